@@ -0,0 +1,111 @@
+// alias.rs
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::CONFIG;
+use crate::error::CliError;
+use crate::parser::is_command_name;
+
+// Aliases & macros ________________________________________________________________________________
+//
+// [alias] in config.toml names a command line (`f = "finish"`) or several of them that run one
+// after another (`standup = ["show ended:yesterday", "show state:started"]`). The alias stands
+// where a command would, the filter before it and the words after it are kept:
+//   pct 3 f            ->  pct 3 finish
+//   pct +work standup  ->  pct +work show ended:yesterday  &  pct +work show state:started
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Alias {
+    Command(String),
+    Macro(Vec<String>),
+}
+
+impl Alias {
+    pub fn command_lines(&self) -> Vec<&str> {
+        match self {
+            Alias::Command(line) => vec![line.as_str()],
+            Alias::Macro(lines) => lines.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+// The command lines to run for the arguments, more than one for a macro.
+pub fn expand_aliases(args: &[String]) -> Result<Vec<Vec<String>>, CliError> {
+    // The completion scripts send half-typed words, which are never expanded:
+    if args.first().is_some_and(|arg| arg == "__complete") {
+        return Ok(vec![args.to_vec()]);
+    }
+    expand(args, &mut Vec::new())
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn expand(args: &[String], expanding: &mut Vec<String>) -> Result<Vec<Vec<String>>, CliError> {
+    // Built-in commands and reports come first, after "--" everything is text:
+    let position = args
+        .iter()
+        .take_while(|arg| *arg != "--" && !is_command_name(arg))
+        .position(|arg| CONFIG.alias.contains_key(arg));
+    let Some(position) = position else {
+        return Ok(vec![args.to_vec()]);
+    };
+    let name = &args[position];
+    if expanding.contains(name) {
+        expanding.push(name.clone());
+        return Err(CliError::AliasLoop(format!(
+            "{} never ends, please change [alias] in config.toml.",
+            expanding.join(" -> ")
+        )));
+    }
+
+    expanding.push(name.clone());
+    let mut lines = Vec::new();
+    for command_line in CONFIG.alias[name].command_lines() {
+        let mut line = args[..position].to_vec();
+        line.extend(split_words(command_line));
+        line.extend_from_slice(&args[position + 1..]);
+        lines.extend(expand(&line, expanding)?);
+    }
+    expanding.pop();
+    Ok(lines)
+}
+
+// Split at whitespace like a shell, "double" or 'single' quotes keep words together.
+pub fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for char in text.chars() {
+        match (quote, char) {
+            (None, '"' | '\'') => {
+                quote = Some(char);
+                word.get_or_insert_with(String::new);
+            }
+            (Some(open), _) if char == open => quote = None,
+            (None, _) if char.is_whitespace() => words.extend(word.take()),
+            _ => word.get_or_insert_with(String::new).push(char),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+#[cfg(test)]
+#[test]
+fn test_expand_aliases() {
+    crate::config::use_test_config();
+    assert_eq!(
+        split_words("add 'Call Bob' \"\" +phone"),
+        vec!["add", "Call Bob", "", "+phone"]
+    );
+
+    let args = |line: &str| line.split(' ').map(String::from).collect::<Vec<_>>();
+    // From the test config:
+    assert_eq!(expand_aliases(&args("3 f")).unwrap(), vec![args("3 finish")]);
+    assert_eq!(
+        expand_aliases(&args("+work standup")).unwrap(),
+        vec![args("+work show ended:yesterday"), args("+work show state:started")]
+    );
+    assert_eq!(expand_aliases(&args("add f")).unwrap(), vec![args("add f")]);
+    let error = expand_aliases(&args("loop")).err().unwrap().to_string();
+    assert!(error.contains("loop -> again -> loop never ends"));
+}
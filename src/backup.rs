@@ -0,0 +1,180 @@
+// backup.rs
+
+use chrono::prelude::*;
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::BackupConfig;
+use crate::database::{create_table, SCHEMA_VERSION};
+use crate::error::BackupError;
+use crate::paths::data_dir;
+
+// Public backup & restore interface _______________________________________________________________
+
+// Copy the open database with SQLite's online backup API, so a backup is consistent even while
+// another pct process writes to tasks.db.
+pub fn backup_database(conn: &Connection, path: Option<PathBuf>) -> Result<String, BackupError> {
+    let path = match path {
+        Some(path) if path.is_dir() => path.join(backup_name(conn)),
+        Some(path) => path,
+        None => {
+            let dir = data_dir()?.join("backups");
+            fs::create_dir_all(&dir)?;
+            dir.join(backup_name(conn))
+        }
+    };
+    conn.backup(DatabaseName::Main, &path, None)?;
+    Ok(format!("Backed up tasks to:\n{}", path.display()))
+}
+
+// Replace all tasks with the ones from a backup. The current database is backed up first, so a
+// restore can itself be undone.
+pub fn restore_database(conn: &Connection, file: &Path) -> Result<String, BackupError> {
+    validate_backup(file)?;
+    let database_file = match conn.path() {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => {
+            return Err(BackupError::InvalidBackup(
+                "Only a database file can be restored".to_string(),
+            ))
+        }
+    };
+
+    let safety_dir = data_dir()?.join("backups");
+    fs::create_dir_all(&safety_dir)?;
+    let safety_file = safety_dir.join(format!("pre-restore-{}", backup_name(conn)));
+    conn.backup(DatabaseName::Main, &safety_file, None)?;
+
+    // The backup API copies page by page into the live file, other connections see the result:
+    let mut target = Connection::open(&database_file)?;
+    target.restore(
+        DatabaseName::Main,
+        file,
+        None::<fn(rusqlite::backup::Progress)>,
+    )?;
+    // Older backups are brought up to the current schema:
+    create_table(conn)?;
+    Ok(format!(
+        "Restored tasks from {}\nThe previous tasks were saved to:\n{}",
+        file.display(),
+        safety_file.display()
+    ))
+}
+
+// Keep one backup per day, made before the first change of the day, and delete the oldest ones.
+pub fn auto_backup(conn: &Connection, config: &BackupConfig) -> Result<(), BackupError> {
+    if config.keep_daily == 0 {
+        return Ok(());
+    }
+    let dir = match &config.dir {
+        Some(dir) => dir.clone(),
+        None => data_dir()?.join("backups").join("daily"),
+    };
+    fs::create_dir_all(&dir)?;
+
+    let name = database_name(conn);
+    let today = dir.join(format!("{}-{}.db", name, Local::now().format("%Y-%m-%d")));
+    if !today.exists() {
+        conn.backup(DatabaseName::Main, &today, None)?;
+    }
+
+    // The date in the names sorts them oldest first:
+    let mut backups: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_daily_backup(path, &name))
+        .collect();
+    backups.sort();
+    let surplus = backups.len().saturating_sub(config.keep_daily);
+    for backup in &backups[..surplus] {
+        fs::remove_file(backup)?;
+    }
+    Ok(())
+}
+
+// Helper functions --------------------------------------------------------------------------------
+// "tasks" for the default workspace, the workspace name for all others.
+fn database_name(conn: &Connection) -> String {
+    match conn.path().map(Path::new).and_then(Path::file_stem) {
+        Some(stem) if !stem.is_empty() => stem.to_string_lossy().to_string(),
+        _ => "tasks".to_string(),
+    }
+}
+
+fn backup_name(conn: &Connection) -> String {
+    format!(
+        "{}-{}.db",
+        database_name(conn),
+        Local::now().format("%Y-%m-%d-%H%M%S")
+    )
+}
+
+fn is_daily_backup(path: &Path, database_name: &str) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    match name
+        .strip_prefix(&format!("{}-", database_name))
+        .and_then(|name| name.strip_suffix(".db"))
+    {
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok(),
+        None => false,
+    }
+}
+
+// A backup must be an intact pocato database that this version of pct knows how to read.
+// Returns its schema version.
+fn validate_backup(file: &Path) -> Result<usize, BackupError> {
+    let invalid =
+        |reason: &str| BackupError::InvalidBackup(format!("{}: {}", file.display(), reason));
+    if !file.is_file() {
+        return Err(invalid("No such file"));
+    }
+    let conn = Connection::open_with_flags(file, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|err| invalid(&err.to_string()))?;
+    if integrity != "ok" {
+        return Err(invalid(&format!("Database is damaged ({})", integrity)));
+    }
+    let has_tasks: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'tasks'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_tasks {
+        return Err(invalid("Not a pocato database"));
+    }
+
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > SCHEMA_VERSION {
+        return Err(BackupError::SchemaTooNew(version, SCHEMA_VERSION));
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+#[test]
+fn test_validate_backup() {
+    let dir = std::env::temp_dir().join(format!("pocato-backup-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).expect("Failed to create test directory");
+
+    let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
+    crate::database::create_table(&conn).expect("Failed to create tables");
+    let backup = dir.join("backup.db");
+    backup_database(&conn, Some(backup.clone())).expect("Failed to back up");
+    assert_eq!(validate_backup(&backup).ok(), Some(SCHEMA_VERSION));
+
+    let not_a_database = dir.join("notes.db");
+    fs::write(&not_a_database, "Buy milk").expect("Failed to write test file");
+    assert!(validate_backup(&not_a_database).is_err());
+
+    let newer = Connection::open(&backup).expect("Failed to open backup");
+    newer
+        .execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION + 1))
+        .expect("Failed to set version");
+    assert!(matches!(
+        validate_backup(&backup),
+        Err(BackupError::SchemaTooNew(..))
+    ));
+    let _ = fs::remove_dir_all(dir);
+}
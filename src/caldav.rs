@@ -0,0 +1,761 @@
+// caldav.rs
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rusqlite::{Connection, Transaction};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::CalDavConfig;
+use crate::crud::{
+    get_task_by_title, get_task_by_uuid, get_tasks_or_empty, remove_task, upsert_task,
+};
+use crate::error::{CrudError, SyncError};
+use crate::ics;
+use crate::paths::runtime_dir;
+use crate::task::Task;
+
+// Two-way sync with a CalDAV task list _____________________________________________________________
+//
+// Every synced task has a row in `caldav_sync` with the server href, the ETag the server returned and
+// a snapshot of the task as it looked after the last sync. A different ETag means the task changed on
+// the server, a different snapshot means it changed locally. If both changed, the server wins.
+pub fn sync_caldav(conn: &Connection, config: &CalDavConfig) -> Result<String, SyncError> {
+    let _lock = SyncLock::acquire()?;
+    let client = Client::new(config);
+    let (listed, refused) = client.list()?;
+    let remote: HashMap<String, Option<String>> = listed.into_iter().collect();
+    let mut report = SyncReport::default();
+    for (href, err) in refused {
+        report.skip(&href, &err);
+    }
+
+    // Each pass is a transaction and each task a savepoint in it. A task that fails is skipped and
+    // reported, it doesn't stop the others and is tried again on the next sync.
+    // Tasks known from previous syncs:
+    let mut transaction = conn.unchecked_transaction().map_err(CrudError::from)?;
+    let states = SyncState::load_all(&transaction)?;
+    let known_hrefs: Vec<&String> = states.iter().map(|state| &state.href).collect();
+    for state in &states {
+        let etag = remote.get(&state.href);
+        sync_task(
+            &mut transaction,
+            &mut report,
+            &state.href,
+            |conn, report| sync_known(&client, conn, report, state, etag),
+        )?;
+    }
+    transaction.commit().map_err(CrudError::from)?;
+
+    // Tasks that are new on the server:
+    let mut transaction = conn.unchecked_transaction().map_err(CrudError::from)?;
+    for (href, etag) in remote
+        .iter()
+        .filter(|(href, _)| !known_hrefs.contains(href))
+    {
+        sync_task(
+            &mut transaction,
+            &mut report,
+            href,
+            |conn, report| match client.download(conn, report, href, etag.as_deref())? {
+                true => Ok(Change::Downloaded),
+                false => Ok(Change::None),
+            },
+        )?;
+    }
+    transaction.commit().map_err(CrudError::from)?;
+
+    // Tasks that are new locally:
+    let mut transaction = conn.unchecked_transaction().map_err(CrudError::from)?;
+    let known_uuids = SyncState::uuids(&transaction)?;
+    let local_tasks = get_tasks_or_empty(&transaction)?;
+    for task in local_tasks
+        .iter()
+        .filter(|task| !known_uuids.contains(&task.uuid))
+    {
+        let href = format!("{}{}.ics", client.collection, task.uuid);
+        sync_task(&mut transaction, &mut report, &href, |conn, report| {
+            client.upload_or_download(conn, report, task, &href, None)
+        })?;
+    }
+    transaction.commit().map_err(CrudError::from)?;
+
+    Ok(format!("Synced with {}:\n{}", config.url, report))
+}
+
+// A task synced before: compare both sides with the state of the last sync.
+fn sync_known(
+    client: &Client,
+    conn: &Connection,
+    report: &mut SyncReport,
+    state: &SyncState,
+    remote_etag: Option<&Option<String>>,
+) -> Result<Change, SyncError> {
+    let local = get_task_by_uuid(conn, &state.uuid)?;
+    let local_changed = local
+        .as_ref()
+        .is_some_and(|task| snapshot(task) != state.snapshot);
+
+    match (remote_etag, local) {
+        // Deleted on both sides:
+        (None, None) => {
+            SyncState::forget(conn, &state.uuid)?;
+            Ok(Change::None)
+        }
+        // Deleted on the server, but edited locally since, so upload it again:
+        (None, Some(task)) if local_changed => {
+            client.upload_or_download(conn, report, &task, &state.href, None)
+        }
+        (None, Some(task)) => {
+            remove_task(conn, &task.uuid)?;
+            SyncState::forget(conn, &state.uuid)?;
+            Ok(Change::DeletedLocally)
+        }
+        // Deleted locally, but edited on the server since, so download it again:
+        (Some(etag), None) if *etag != state.etag => {
+            client.download(conn, report, &state.href, etag.as_deref())?;
+            Ok(Change::Downloaded)
+        }
+        (Some(_), None) => match client.delete(&state.href, state.etag.as_deref()) {
+            // Edited on the server in the meantime, so it comes back:
+            Err(err) if is_precondition_failed(&err) => {
+                client.download(conn, report, &state.href, None)?;
+                Ok(Change::Downloaded)
+            }
+            result => {
+                result?;
+                SyncState::forget(conn, &state.uuid)?;
+                Ok(Change::DeletedRemotely)
+            }
+        },
+        (Some(etag), Some(task)) => match (*etag != state.etag, local_changed) {
+            (false, false) => Ok(Change::None),
+            (false, true) => {
+                client.upload_or_download(conn, report, &task, &state.href, state.etag.as_deref())
+            }
+            (true, false) => {
+                client.download(conn, report, &state.href, etag.as_deref())?;
+                Ok(Change::Downloaded)
+            }
+            (true, true) => {
+                client.download(conn, report, &state.href, etag.as_deref())?;
+                Ok(Change::Conflict)
+            }
+        },
+    }
+}
+
+// Run the sync of one task in a savepoint, which is rolled back if it fails.
+fn sync_task(
+    transaction: &mut Transaction,
+    report: &mut SyncReport,
+    href: &str,
+    sync: impl FnOnce(&Connection, &mut SyncReport) -> Result<Change, SyncError>,
+) -> Result<(), SyncError> {
+    let savepoint = transaction.savepoint().map_err(CrudError::from)?;
+    match sync(&savepoint, report) {
+        Ok(change) => {
+            savepoint.commit().map_err(CrudError::from)?;
+            report.count(change);
+        }
+        Err(err) => report.skip(href, &err),
+    }
+    Ok(())
+}
+
+// CalDAV client ___________________________________________________________________________________
+struct Client {
+    agent: ureq::Agent,
+    collection: String,
+    authorization: Option<String>,
+}
+
+impl Client {
+    fn new(config: &CalDavConfig) -> Self {
+        let authorization = config.username.as_ref().map(|username| {
+            let credentials = format!("{}:{}", username, config.password.as_deref().unwrap_or(""));
+            format!("Basic {}", STANDARD.encode(credentials))
+        });
+        let collection = match config.url.ends_with('/') {
+            true => config.url.clone(),
+            false => format!("{}/", config.url),
+        };
+        Client {
+            agent: ureq::AgentBuilder::new().redirects(0).build(),
+            collection,
+            authorization,
+        }
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        let request = self.agent.request(method, url);
+        match &self.authorization {
+            Some(authorization) => request.set("Authorization", authorization),
+            None => request,
+        }
+    }
+
+    // List all calendar objects of the collection as (href, etag) pairs, and the hrefs that were
+    // refused as they point to another server.
+    fn list(&self) -> Result<Listing, SyncError> {
+        let body = "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+            <d:propfind xmlns:d=\"DAV:\"><d:prop><d:getetag/><d:resourcetype/></d:prop></d:propfind>";
+        let response = self
+            .request("PROPFIND", &self.collection)
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(body)?;
+        let multistatus = response.into_string()?;
+
+        let (mut objects, mut refused) = (Vec::new(), Vec::new());
+        for response in xml_elements(&multistatus, "response") {
+            let Some(href) = xml_elements(response, "href")
+                .first()
+                .map(|href| xml_text(href))
+            else {
+                continue;
+            };
+            // Skip the collection itself:
+            if !xml_elements(response, "collection").is_empty() {
+                continue;
+            }
+            let etag = xml_elements(response, "getetag")
+                .first()
+                .map(|etag| xml_text(etag))
+                .filter(|etag| !etag.is_empty());
+            match self.resolve(&href) {
+                Ok(url) => objects.push((url, etag)),
+                Err(err) => refused.push((href, err)),
+            }
+        }
+        Ok((objects, refused))
+    }
+
+    // Download a calendar object and upsert its VTODO. Returns false if it contains no VTODO.
+    fn download(
+        &self,
+        conn: &Connection,
+        report: &mut SyncReport,
+        href: &str,
+        listed_etag: Option<&str>,
+    ) -> Result<bool, SyncError> {
+        let response = self.request("GET", href).call()?;
+        let etag = response.header("ETag").or(listed_etag).map(str::to_string);
+        let body = response.into_string()?;
+
+        let Some(mut task) = ics::from_ics(&body)?.into_iter().next() else {
+            return Ok(false);
+        };
+        // Titles are unique, so a server task can't take the title of another local task:
+        if let Some(title) = free_title(conn, &task)? {
+            report
+                .renamed
+                .push(format!("'{}' to '{}'", task.title, title));
+            task.title = title;
+        }
+        upsert_task(conn, &task)?;
+        // Store the task as it was saved, so the next sync doesn't see a local change:
+        if let Some(task) = get_task_by_uuid(conn, &task.uuid)? {
+            SyncState::save(conn, &task, href, etag)?;
+        }
+        Ok(true)
+    }
+
+    // Upload a task, with If-Match to not overwrite changes on the server that we haven't seen yet.
+    fn upload(
+        &self,
+        conn: &Connection,
+        task: &Task,
+        href: &str,
+        etag: Option<&str>,
+    ) -> Result<(), SyncError> {
+        let request = self
+            .request("PUT", href)
+            .set("Content-Type", "text/calendar; charset=utf-8");
+        let request = match etag {
+            Some(etag) => request.set("If-Match", etag),
+            None => request.set("If-None-Match", "*"),
+        };
+        let response = request.send_string(&ics::to_ics(std::slice::from_ref(task)))?;
+        let etag = response.header("ETag").map(str::to_string);
+        SyncState::save(conn, task, href, etag)?;
+        Ok(())
+    }
+
+    // A 412 on If-Match or If-None-Match means the server has a version we haven't seen yet. The
+    // server wins then, like in any other conflict.
+    fn upload_or_download(
+        &self,
+        conn: &Connection,
+        report: &mut SyncReport,
+        task: &Task,
+        href: &str,
+        etag: Option<&str>,
+    ) -> Result<Change, SyncError> {
+        match self.upload(conn, task, href, etag) {
+            Ok(()) => Ok(Change::Uploaded),
+            Err(err) if is_precondition_failed(&err) => {
+                self.download(conn, report, href, None)?;
+                Ok(Change::Conflict)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn delete(&self, href: &str, etag: Option<&str>) -> Result<(), SyncError> {
+        let request = self.request("DELETE", href);
+        let request = match etag {
+            Some(etag) => request.set("If-Match", etag),
+            None => request,
+        };
+        match request.call() {
+            // Already gone:
+            Err(ureq::Error::Status(404, _)) => Ok(()),
+            result => result.map(|_| ()).map_err(SyncError::from),
+        }
+    }
+
+    // Turn an href from a multistatus response into an absolute URL. Every request carries the
+    // credentials, so an href on another server is refused.
+    fn resolve(&self, href: &str) -> Result<String, SyncError> {
+        let authority_start = self.collection.find("://").map_or(0, |index| index + 3);
+        let origin_end = self.collection[authority_start..]
+            .find('/')
+            .map_or(self.collection.len(), |index| authority_start + index);
+        let origin = &self.collection[..origin_end];
+        if href.starts_with("http://") || href.starts_with("https://") {
+            let same_origin = href
+                .get(..origin.len())
+                .is_some_and(|href_origin| href_origin.eq_ignore_ascii_case(origin));
+            return match same_origin && href[origin.len()..].starts_with('/') {
+                true => Ok(href.to_string()),
+                false => Err(SyncError::OtherServer(format!(
+                    "{} isn't on {}, the server's answer was ignored",
+                    href, origin
+                ))),
+            };
+        }
+        match href.starts_with('/') {
+            true => Ok(format!("{}{}", origin, href)),
+            false => Ok(format!("{}{}", self.collection, href)),
+        }
+    }
+}
+
+// Sync state ______________________________________________________________________________________
+struct SyncState {
+    uuid: String,
+    href: String,
+    etag: Option<String>,
+    snapshot: String,
+}
+
+impl SyncState {
+    fn load_all(conn: &Connection) -> Result<Vec<SyncState>, CrudError> {
+        let mut stmt = conn.prepare("SELECT uuid, href, etag, snapshot FROM caldav_sync")?;
+        let states = stmt.query_map((), |row| {
+            Ok(SyncState {
+                uuid: row.get(0)?,
+                href: row.get(1)?,
+                etag: row.get(2)?,
+                snapshot: row.get(3)?,
+            })
+        })?;
+        Ok(states.collect::<Result<_, _>>()?)
+    }
+
+    fn uuids(conn: &Connection) -> Result<Vec<String>, CrudError> {
+        let mut stmt = conn.prepare("SELECT uuid FROM caldav_sync")?;
+        let uuids = stmt.query_map((), |row| row.get(0))?;
+        Ok(uuids.collect::<Result<_, _>>()?)
+    }
+
+    fn save(
+        conn: &Connection,
+        task: &Task,
+        href: &str,
+        etag: Option<String>,
+    ) -> Result<(), CrudError> {
+        conn.execute(
+            "INSERT OR REPLACE INTO caldav_sync (uuid, href, etag, snapshot) VALUES (?1, ?2, ?3, ?4)",
+            (task.uuid.clone(), href, etag, snapshot(task)),
+        )?;
+        Ok(())
+    }
+
+    fn forget(conn: &Connection, uuid: &str) -> Result<(), CrudError> {
+        conn.execute("DELETE FROM caldav_sync WHERE uuid = ?", [uuid])?;
+        Ok(())
+    }
+}
+
+// Objects of a collection as (href, etag) and the refused hrefs
+type Listing = (Vec<(String, Option<String>)>, Vec<(String, SyncError)>);
+
+// What syncing a single task did
+enum Change {
+    None,
+    Downloaded,
+    Uploaded,
+    DeletedLocally,
+    DeletedRemotely,
+    /// Changed on both sides, the server version was kept
+    Conflict,
+}
+
+#[derive(Default)]
+struct SyncReport {
+    downloaded: usize,
+    uploaded: usize,
+    deleted_locally: usize,
+    deleted_remotely: usize,
+    conflicts: usize,
+    /// Server tasks that got a new title, as "'old' to 'new'"
+    renamed: Vec<String>,
+    /// Tasks that couldn't be synced and why
+    skipped: Vec<String>,
+}
+
+impl SyncReport {
+    fn count(&mut self, change: Change) {
+        match change {
+            Change::None => (),
+            Change::Downloaded => self.downloaded += 1,
+            Change::Uploaded => self.uploaded += 1,
+            Change::DeletedLocally => self.deleted_locally += 1,
+            Change::DeletedRemotely => self.deleted_remotely += 1,
+            Change::Conflict => self.conflicts += 1,
+        }
+    }
+
+    fn skip(&mut self, href: &str, err: &SyncError) {
+        let reason = err.to_string().replace('\n', " ");
+        self.skipped.push(format!("{}: {}", href, reason));
+    }
+}
+
+impl std::fmt::Display for SyncReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "  {} downloaded, {} uploaded, {} deleted locally, {} deleted on the server, \
+            {} conflicts (server version kept)",
+            self.downloaded,
+            self.uploaded,
+            self.deleted_locally,
+            self.deleted_remotely,
+            self.conflicts
+        )?;
+        for renamed in &self.renamed {
+            write!(f, "\n  Renamed {}, the title was taken", renamed)?;
+        }
+        for skipped in &self.skipped {
+            write!(f, "\n  Skipped {}", skipped)?;
+        }
+        Ok(())
+    }
+}
+
+// Only one sync may run at a time, e.g. a cron job and a `pct sync caldav` by hand. The lock file
+// lives in the runtime dir and is removed again when the sync ends.
+struct SyncLock(PathBuf);
+
+impl SyncLock {
+    // A lock this old is left over from a sync that crashed:
+    const STALE_AFTER: Duration = Duration::from_secs(10 * 60);
+
+    fn acquire() -> Result<Self, SyncError> {
+        let dir = runtime_dir();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("caldav-sync.lock");
+
+        let age = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| modified.elapsed().unwrap_or_default());
+        if age.is_ok_and(|age| age > SyncLock::STALE_AFTER) {
+            fs::remove_file(&path)?;
+        }
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => Ok(SyncLock(path)),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => Err(SyncError::Locked(format!(
+                "Another sync is running. If it isn't, remove {}",
+                path.display()
+            ))),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl Drop for SyncLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// Helper functions --------------------------------------------------------------------------------
+
+fn is_precondition_failed(err: &SyncError) -> bool {
+    matches!(err, SyncError::Http(err) if matches!(**err, ureq::Error::Status(412, _)))
+}
+
+// A free title like "Call Bob (2)" if another task has the task's title already.
+fn free_title(conn: &Connection, task: &Task) -> Result<Option<String>, CrudError> {
+    let is_taken = |title: &str| -> Result<bool, CrudError> {
+        Ok(get_task_by_title(conn, title)?.is_some_and(|other| other.uuid != task.uuid))
+    };
+    if !is_taken(&task.title)? {
+        return Ok(None);
+    }
+    let mut number = 2;
+    while is_taken(&format!("{} ({})", task.title, number))? {
+        number += 1;
+    }
+    Ok(Some(format!("{} ({})", task.title, number)))
+}
+
+// The synced fields of a task, without the DTSTAMP that changes on every export.
+fn snapshot(task: &Task) -> String {
+    ics::to_vtodo(task)
+        .into_iter()
+        .filter(|line| !line.starts_with("DTSTAMP:"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Minimal XML scanning for WebDAV multistatus responses. Namespace prefixes are ignored, so it
+// doesn't matter if a server uses <d:href>, <D:href> or <href xmlns="DAV:">.
+fn xml_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut elements = Vec::new();
+    let mut position = 0;
+    while let Some(tag) = next_tag(xml, position) {
+        position = tag.end;
+        if tag.closing || local_name(tag.name) != name {
+            continue;
+        }
+        if tag.self_closing {
+            elements.push("");
+            continue;
+        }
+        // Find the matching closing tag:
+        let mut depth = 1;
+        while let Some(inner) = next_tag(xml, position) {
+            position = inner.end;
+            if local_name(inner.name) != name || inner.self_closing {
+                continue;
+            }
+            depth += if inner.closing { -1 } else { 1 };
+            if depth == 0 {
+                elements.push(&xml[tag.end..inner.start]);
+                break;
+            }
+        }
+    }
+    elements
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    start: usize,
+    end: usize,
+    closing: bool,
+    self_closing: bool,
+}
+
+fn next_tag(xml: &str, from: usize) -> Option<Tag<'_>> {
+    let start = from + xml.get(from..)?.find('<')?;
+    let end = start + xml[start..].find('>')? + 1;
+    let inner = &xml[start + 1..end - 1];
+    let closing = inner.starts_with('/');
+    let name = inner
+        .trim_start_matches('/')
+        .split(|char: char| char.is_whitespace() || char == '/')
+        .next()
+        .unwrap_or("");
+    Some(Tag {
+        name,
+        start,
+        end,
+        closing,
+        self_closing: inner.ends_with('/'),
+    })
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn xml_text(text: &str) -> String {
+    text.trim()
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+#[test]
+fn test_sync_with_mock_server() {
+    use std::sync::{Arc, Mutex};
+    use tiny_http::{Header, Response, Server};
+
+    crate::config::use_test_config();
+
+    // In-process CalDAV server that keeps calendar objects in memory as href -> (etag, body):
+    let server = Server::http("127.0.0.1:0").expect("Failed to start mock server");
+    let url = format!("http://{}/tasks/", server.server_addr());
+    let objects: Arc<Mutex<HashMap<String, (String, String)>>> = Arc::default();
+    let server_objects = Arc::clone(&objects);
+    std::thread::spawn(move || {
+        let mut counter = 0;
+        for mut request in server.incoming_requests() {
+            let mut objects = server_objects.lock().unwrap();
+            let href = request.url().to_string();
+            let if_match = request
+                .headers()
+                .iter()
+                .find(|header| header.field.equiv("If-Match"))
+                .map(|header| header.value.to_string());
+            let changed = if_match
+                .is_some_and(|etag| objects.get(&href).map(|(current, _)| current) != Some(&etag));
+            let response = match request.method().as_str() {
+                "PUT" | "DELETE" if changed => Response::from_string("").with_status_code(412),
+                "PROPFIND" => {
+                    let mut xml = "<d:multistatus xmlns:d=\"DAV:\"><d:response><d:href>/tasks/\
+                        </d:href><d:propstat><d:prop><d:resourcetype><d:collection/>\
+                        </d:resourcetype></d:prop></d:propstat></d:response>"
+                        .to_string();
+                    for (href, (etag, _)) in objects.iter() {
+                        xml += &format!(
+                            "<d:response><d:href>{}</d:href><d:propstat><d:prop><d:getetag>\
+                            {}</d:getetag></d:prop></d:propstat></d:response>",
+                            href,
+                            etag.replace('"', "&quot;")
+                        );
+                    }
+                    Response::from_string(xml + "</d:multistatus>").with_status_code(207)
+                }
+                "GET" => match objects.get(&href) {
+                    Some((etag, body)) => Response::from_string(body.clone())
+                        .with_header(Header::from_bytes("ETag", etag.as_str()).unwrap()),
+                    None => Response::from_string("").with_status_code(404),
+                },
+                "PUT" => {
+                    let mut body = String::new();
+                    request.as_reader().read_to_string(&mut body).unwrap();
+                    counter += 1;
+                    let etag = format!("\"{}\"", counter);
+                    objects.insert(href, (etag.clone(), body));
+                    Response::from_string("")
+                        .with_status_code(201)
+                        .with_header(Header::from_bytes("ETag", etag.as_str()).unwrap())
+                }
+                "DELETE" => {
+                    objects.remove(&href);
+                    Response::from_string("").with_status_code(204)
+                }
+                _ => Response::from_string("").with_status_code(405),
+            };
+            drop(objects);
+            request.respond(response).unwrap();
+        }
+    });
+
+    let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
+    crate::database::create_table(&conn).expect("Failed to create tables");
+    let config = CalDavConfig {
+        url,
+        username: Some("user".to_string()),
+        password: Some("secret".to_string()),
+    };
+    crate::crud::create_task(&conn, "Local task".to_string(), &[]).unwrap();
+
+    // First sync uploads the local task:
+    let report = sync_caldav(&conn, &config).unwrap();
+    assert!(report.contains("0 downloaded, 1 uploaded"));
+    assert_eq!(objects.lock().unwrap().len(), 1);
+
+    // Change it on the server and add a new task there:
+    {
+        let mut objects = objects.lock().unwrap();
+        let (_, (etag, body)) = objects.iter_mut().next().unwrap();
+        *body = body.replace("SUMMARY:Local task", "SUMMARY:Edited remotely");
+        *etag = "\"edited\"".to_string();
+        objects.insert(
+            "/tasks/remote.ics".to_string(),
+            (
+                "\"remote\"".to_string(),
+                "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:remote-uid\r\nSUMMARY:Remote task\r\n\
+                STATUS:COMPLETED\r\nEND:VTODO\r\nEND:VCALENDAR\r\n"
+                    .to_string(),
+            ),
+        );
+    }
+    let report = sync_caldav(&conn, &config).unwrap();
+    assert!(report.contains("2 downloaded, 0 uploaded"));
+    let tasks = get_tasks_or_empty(&conn).unwrap();
+    assert_eq!(tasks[0].title, "Edited remotely");
+    assert_eq!(tasks[1].title, "Remote task");
+    assert_eq!(tasks[1].state.to_string(), "Finished");
+
+    // Nothing changed on either side:
+    let report = sync_caldav(&conn, &config).unwrap();
+    assert!(report.contains("0 downloaded, 0 uploaded, 0 deleted locally, 0 deleted on the server"));
+
+    // Deleting a task locally deletes it on the server:
+    remove_task(&conn, "remote-uid").unwrap();
+    let report = sync_caldav(&conn, &config).unwrap();
+    assert!(report.contains("1 deleted on the server"));
+    assert!(!objects.lock().unwrap().contains_key("/tasks/remote.ics"));
+    assert_eq!(SyncState::uuids(&conn).unwrap().len(), 1);
+
+    // An upload with a stale ETag gets a 412 and the server version wins:
+    let client = Client::new(&config);
+    let mut task = get_tasks_or_empty(&conn).unwrap()[0].clone();
+    task.title = "Edited locally".to_string();
+    let href = format!("{}{}.ics", client.collection, task.uuid);
+    let mut report = SyncReport::default();
+    let change = client.upload_or_download(&conn, &mut report, &task, &href, Some("\"old\""));
+    assert!(matches!(change, Ok(Change::Conflict)));
+    let (_, body) = objects.lock().unwrap()[&format!("/tasks/{}.ics", task.uuid)].clone();
+    assert!(body.contains("SUMMARY:Edited remotely"));
+
+    // Broken objects and titles taken by other tasks don't stop the sync:
+    crate::crud::create_task(&conn, "Twin".to_string(), &[]).unwrap();
+    {
+        let mut objects = objects.lock().unwrap();
+        let vtodo = |uid: &str, summary: &str| {
+            format!(
+                "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nUID:{}\r\n{}END:VTODO\r\nEND:VCALENDAR\r\n",
+                uid, summary
+            )
+        };
+        objects.insert(
+            "/tasks/broken.ics".to_string(),
+            ("\"broken\"".to_string(), vtodo("broken-uid", "")),
+        );
+        objects.insert(
+            "/tasks/twin.ics".to_string(),
+            (
+                "\"twin\"".to_string(),
+                vtodo("twin-uid", "SUMMARY:Twin\r\n"),
+            ),
+        );
+        // Requests to other hosts would carry the credentials:
+        objects.insert(
+            "http://evil.example/tasks/steal.ics".to_string(),
+            (
+                "\"steal\"".to_string(),
+                vtodo("steal-uid", "SUMMARY:Steal\r\n"),
+            ),
+        );
+    }
+    let report = sync_caldav(&conn, &config).unwrap();
+    assert!(report.contains("Renamed 'Twin' to 'Twin (2)'"));
+    assert!(report.contains("Skipped http://evil.example/tasks/steal.ics: "));
+    assert!(get_task_by_title(&conn, "Steal").unwrap().is_none());
+    assert!(report.contains("Skipped http://") && report.contains("broken.ics: "));
+    assert!(get_task_by_title(&conn, "Twin (2)").unwrap().is_some());
+}
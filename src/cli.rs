@@ -1,16 +1,33 @@
 // cli.rs
 
+mod alias;
+mod backup;
+mod caldav;
+mod color;
+mod completion;
 mod crud;
 mod lexer;
 mod database;
 mod error;
+mod exchange;
+mod filter;
+mod font;
+mod parser;
+mod paths;
+mod picker;
+mod prompt;
+mod ics;
 mod config;
+mod report;
+mod search;
+mod shell;
 mod task;
+mod theme;
+mod taskwarrior;
+mod todotxt;
+mod workspace;
 
 fn main() {
-    // Establish SQLite Database connection
-    let conn = database::init_db();
-
-    // Parse CLI arguments & call CRUD methods
-    lexer::lexer_handler(&conn);
+    // Parse CLI arguments, open the workspace's database & call CRUD methods
+    lexer::lexer_handler();
 }
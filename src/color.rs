@@ -0,0 +1,89 @@
+// color.rs
+
+use clap::ValueEnum;
+use once_cell::sync::OnceCell;
+use std::env;
+use std::fmt::Display;
+use std::io::IsTerminal;
+
+// Colored output __________________________________________________________________________________
+//
+// Messages and tables are built with ANSI colors, which are taken out again right before printing
+// if color is off. With --color auto (the default) that is the case when $NO_COLOR is set or the
+// output isn't a terminal, $CLICOLOR_FORCE turns it on for pipes.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+static COLOR_CHOICE: OnceCell<ColorChoice> = OnceCell::new();
+
+// Call once before anything is printed.
+pub fn init(choice: ColorChoice) {
+    let _ = COLOR_CHOICE.set(choice);
+    colored::control::set_override(enabled(std::io::stdout().is_terminal()));
+}
+
+// Text for stdout, without colors if they're off.
+pub fn stdout(text: impl Display) -> String {
+    paint(text, std::io::stdout().is_terminal())
+}
+
+// Text for stderr, without colors if they're off.
+pub fn stderr(text: impl Display) -> String {
+    paint(text, std::io::stderr().is_terminal())
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn paint(text: impl Display, is_terminal: bool) -> String {
+    match enabled(is_terminal) {
+        true => text.to_string(),
+        false => strip_ansi(&text.to_string()),
+    }
+}
+
+fn enabled(is_terminal: bool) -> bool {
+    let env_value = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+    match COLOR_CHOICE.get().copied().unwrap_or(ColorChoice::Auto) {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto if env_value("NO_COLOR").is_some() => false,
+        ColorChoice::Auto => match env_value("CLICOLOR_FORCE") {
+            Some(force) => force != "0",
+            None => is_terminal,
+        },
+    }
+}
+
+// Remove ANSI escape sequences like "\x1b[1;34m".
+fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(char) = chars.next() {
+        if char != '\x1b' {
+            plain.push(char);
+            continue;
+        }
+        // Parameters ('0'-'9', ';' ...) up to and including the final byte ('@'-'~'):
+        if chars.next_if_eq(&'[').is_some() {
+            for char in chars.by_ref() {
+                if ('@'..='~').contains(&char) {
+                    break;
+                }
+            }
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+#[test]
+fn test_strip_ansi() {
+    assert_eq!(
+        strip_ansi("Started:\n\x1b[33m\u{f044}\x1b[0m  \x1b[1;34mClean room\x1b[0m (#1)"),
+        "Started:\n\u{f044}  Clean room (#1)"
+    );
+    assert_eq!(strip_ansi("\x1b[1;38;2;38;139;210mID\x1b[0m │ 1"), "ID │ 1");
+}
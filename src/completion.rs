@@ -0,0 +1,453 @@
+// completion.rs
+
+use clap::ValueEnum;
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::alias::expand_aliases;
+use crate::config::{list_settings, CONFIG};
+use crate::crud::get_tasks_or_empty;
+use crate::error::CliError;
+use crate::exchange::Format;
+use crate::parser::{is_command_name, COMMANDS};
+use crate::report::Column;
+use crate::task::Task;
+use crate::workspace::workspace_names;
+
+// Shell completions _______________________________________________________________________________
+//
+// `pct completions <shell>` prints a script that asks `pct __complete <words>` for candidates on
+// every <TAB>. The words are the command line up to the cursor, the last one is the word being
+// completed (empty right after a space). Candidates are printed as "value\tdescription", one per
+// line and already narrowed down to the current word, so task ids, tags and projects come straight
+// from the database.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+const BASH_SCRIPT: &str = r#"# pct completions for bash, add to ~/.bashrc: source <(pct completions bash)
+_pct() {
+    local line=${COMP_LINE:0:COMP_POINT} words value description
+    read -ra words <<< "$line"
+    [[ $line == *[[:space:]] ]] && words+=("")
+    # bash splits words at ':' and '=', only the part after them gets replaced:
+    local current=${words[${#words[@]}-1]}
+    local rest=${current##*[:=]}
+    local prefix=${current%"$rest"}
+    local -a values=() described=()
+    while IFS=$'\t' read -r value description; do
+        [[ -n $value ]] || continue
+        values+=("${value#"$prefix"}")
+        described+=("${value#"$prefix"}  -- $description")
+    done < <(pct __complete "${words[@]:1}" 2>/dev/null)
+    # Titles and descriptions are only shown in the list, a single candidate is inserted as is:
+    case ${#values[@]} in
+        0) COMPREPLY=() ;;
+        1) COMPREPLY=("${values[0]}") ;;
+        *) COMPREPLY=("${described[@]}") ;;
+    esac
+}
+complete -o default -F _pct pct
+"#;
+
+const ZSH_SCRIPT: &str = r#"#compdef pct
+# pct completions for zsh, add to ~/.zshrc: source <(pct completions zsh)
+_pct() {
+    local line
+    local -a candidates
+    for line in "${(@f)$(pct __complete "${(@Q)words[2,CURRENT]}" 2>/dev/null)}"; do
+        [[ -n $line ]] || continue
+        candidates+=("${${line%%$'\t'*}//:/\\:}:${line#*$'\t'}")
+    done
+    if (( ${#candidates} )); then
+        _describe -V -t values pct candidates
+    else
+        _files
+    fi
+}
+if [[ $funcstack[1] == _pct ]]; then
+    _pct "$@"
+else
+    compdef _pct pct
+fi
+"#;
+
+const FISH_SCRIPT: &str = r#"# pct completions for fish, save as ~/.config/fish/completions/pct.fish:
+#   pct completions fish > ~/.config/fish/completions/pct.fish
+function __pct_complete
+    set -l words (commandline -opc) (commandline -ct)
+    set -l candidates (pct __complete $words[2..-1] 2>/dev/null)
+    if set -q candidates[1]
+        printf '%s\n' $candidates
+    else
+        __fish_complete_path (commandline -ct)
+    end
+end
+complete -c pct -f -a '(__pct_complete)'
+"#;
+
+pub fn completion_script(shell: Shell) -> String {
+    let script = match shell {
+        Shell::Bash => BASH_SCRIPT,
+        Shell::Zsh => ZSH_SCRIPT,
+        Shell::Fish => FISH_SCRIPT,
+    };
+    script.trim_end().to_string()
+}
+
+// The value of `--workspace` in the words, so ids and tags come from the workspace being typed.
+pub fn workspace_flag(words: &[String]) -> Option<&str> {
+    let before = &words[..words.len().saturating_sub(1)];
+    before
+        .iter()
+        .enumerate()
+        .find_map(|(index, word)| match word.as_str() {
+            "--workspace" => before.get(index + 1).map(String::as_str),
+            word => word.strip_prefix("--workspace="),
+        })
+}
+
+pub fn complete(conn: &Connection, words: &[String]) -> Result<String, CliError> {
+    let (current, before) = match words.split_last() {
+        Some((current, before)) => (current.as_str(), before),
+        None => ("", words),
+    };
+    let candidates = candidates(conn, current, before)?;
+    let lines: Vec<String> = candidates
+        .into_iter()
+        .filter(|(value, _)| value.starts_with(current))
+        .map(|(value, description)| format!("{}\t{}", value, description))
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+// Helper functions --------------------------------------------------------------------------------
+type Candidates = Vec<(String, String)>;
+
+const GLOBAL_OPTIONS: [(&str, &str); 6] = [
+    (
+        "--workspace",
+        "Use this workspace instead of the active one",
+    ),
+    ("--color", "Colored output: auto, always or never"),
+    ("--config", "Override a setting for this run"),
+    ("--yes", "Answer yes to confirmations"),
+    ("--no-input", "Never ask, fail instead"),
+    ("--help", "Show the help"),
+];
+
+const VALUE_OPTIONS: [&str; 5] = [
+    "--format",
+    "--from",
+    "--columns",
+    "--filter",
+    "--older-than",
+];
+
+fn candidates(conn: &Connection, current: &str, before: &[String]) -> Result<Candidates, CliError> {
+    // Global options come first, their values are completed right here:
+    let mut position = 0;
+    while let Some(word) = before.get(position).filter(|word| word.starts_with("--")) {
+        position += match word.as_str() {
+            "--workspace" | "--color" | "--config" => 2,
+            _ => 1,
+        };
+    }
+    let global_value = match position > before.len() {
+        true => before.last().map(String::as_str),
+        false => None,
+    };
+    match global_value {
+        Some("--workspace") => return workspaces(),
+        Some("--color") => return Ok(variants::<crate::color::ColorChoice>("", "color")),
+        Some(_) => return Ok(Vec::new()),
+        None => {}
+    }
+    // An alias completes like the command line it stands for:
+    let words = &before[position.min(before.len())..];
+    let expanded = match expand_aliases(words) {
+        Ok(mut lines) if lines.len() == 1 => lines.remove(0),
+        _ => words.to_vec(),
+    };
+    let words = expanded.as_slice();
+
+    let command = words.iter().position(|word| is_command_name(word));
+    let Some(index) = command else {
+        // Still in front of the command: a filter, global options or the command itself.
+        return Ok(match current.chars().next() {
+            Some('-') if current.starts_with("--") && words.is_empty() => GLOBAL_OPTIONS
+                .iter()
+                .map(|(option, about)| (option.to_string(), about.to_string()))
+                .collect(),
+            Some('0'..='9') => task_ids(conn, |_| true)?,
+            Some('+' | '-') => tags(conn, current)?,
+            _ if current.contains(':') => attribute_values(conn, current)?,
+            _ => commands(),
+        });
+    };
+    let (filter, command, arguments) = (&words[..index], &words[index], &words[index + 1..]);
+
+    // Values of options like `--format ics` or `--format=ics`:
+    if let Some(option) = arguments
+        .last()
+        .filter(|word| VALUE_OPTIONS.contains(&word.as_str()))
+    {
+        return option_values(option, "", current);
+    }
+    if let Some((option, value)) = current
+        .split_once('=')
+        .filter(|_| current.starts_with("--"))
+    {
+        return option_values(option, &format!("{}=", option), value);
+    }
+    if current.starts_with("--") {
+        return Ok(options(command));
+    }
+    let words: Vec<&String> = arguments
+        .iter()
+        .filter(|word| !word.starts_with("--"))
+        .collect();
+    match (command.as_str(), words.as_slice()) {
+        ("finish" | "start" | "block" | "someday" | "cancel" | "pause", [])
+            if filter.is_empty() =>
+        {
+            task_ids(conn, |task| !task.state.is_closed())
+        }
+        ("info" | "show" | "modify" | "delete" | "notes" | "annotate", []) if filter.is_empty() => {
+            task_ids(conn, |_| true)
+        }
+        ("sync", []) => Ok(values(&[("caldav", "Sync with the CalDAV task list")])),
+        ("trash", []) => Ok(values(&[(
+            "empty",
+            "Delete the tasks in the trash for good",
+        )])),
+        ("completions", []) => Ok(variants::<Shell>("", "shell")),
+        ("workspace", []) => Ok(values(&[
+            ("list", "List all workspaces"),
+            ("create", "Create a new workspace"),
+            ("switch", "Make a workspace the active one"),
+        ])),
+        ("workspace", [action]) if action.as_str() == "switch" => workspaces(),
+        ("config", []) => Ok(values(&[
+            ("list", "Show all settings in effect"),
+            ("get", "Show a setting"),
+            ("set", "Change a setting"),
+            ("validate", "Check all config files"),
+            ("detect-font", "Check for a Nerd Font and switch icons"),
+        ])),
+        ("config", [action]) if ["get", "set"].contains(&action.as_str()) => config_keys(),
+        // Files are left to the shell:
+        ("import" | "restore" | "backup", _) => Ok(Vec::new()),
+        _ => match current.chars().next() {
+            Some('+' | '-') => tags(conn, current),
+            _ if current.contains(':') => attribute_values(conn, current),
+            _ => Ok(Vec::new()),
+        },
+    }
+}
+
+fn commands() -> Candidates {
+    let mut candidates: Candidates = COMMANDS
+        .iter()
+        .map(|(name, _, about)| (name.to_string(), about.to_string()))
+        .collect();
+    for (name, report) in &CONFIG.report {
+        let filter = report.filter.as_deref().unwrap_or("all tasks");
+        candidates.push((name.clone(), format!("Report: {}", filter)));
+    }
+    for (name, alias) in &CONFIG.alias {
+        let command_lines = alias.command_lines().join("; ");
+        candidates.push((name.clone(), format!("Alias: {}", command_lines)));
+    }
+    candidates
+}
+
+// "--archived", "--filter" ... as listed in the usage of the command.
+fn options(command: &str) -> Candidates {
+    let Some((_, usage, _)) = COMMANDS.iter().find(|(name, _, _)| *name == command) else {
+        return Vec::new();
+    };
+    let mut options: Vec<String> = usage
+        .split(|char: char| !(char.is_ascii_alphanumeric() || char == '-'))
+        .filter(|word| word.starts_with("--"))
+        .map(String::from)
+        .collect();
+    options.dedup();
+    options
+        .into_iter()
+        .map(|option| (option, command.to_string()))
+        .collect()
+}
+
+fn option_values(option: &str, prefix: &str, value: &str) -> Result<Candidates, CliError> {
+    Ok(match option {
+        "--format" | "--from" => variants::<Format>(prefix, "format"),
+        // A comma separated list, the columns before the last comma are kept:
+        "--columns" => {
+            let done = value.rfind(',').map_or("", |comma| &value[..=comma]);
+            variants::<Column>(&format!("{}{}", prefix, done), "column")
+        }
+        _ => Vec::new(),
+    })
+}
+
+fn variants<T: ValueEnum>(prefix: &str, what: &str) -> Candidates {
+    T::value_variants()
+        .iter()
+        .filter_map(|variant| variant.to_possible_value())
+        .map(|value| (format!("{}{}", prefix, value.get_name()), what.to_string()))
+        .collect()
+}
+
+fn values(values: &[(&str, &str)]) -> Candidates {
+    values
+        .iter()
+        .map(|(value, about)| (value.to_string(), about.to_string()))
+        .collect()
+}
+
+// Ids of the tasks in the list with their titles.
+fn task_ids(conn: &Connection, keep: impl Fn(&Task) -> bool) -> Result<Candidates, CliError> {
+    let mut tasks = get_tasks_or_empty(conn)?;
+    tasks.sort_by_key(|task| task.id);
+    Ok(tasks
+        .iter()
+        .filter(|task| keep(task))
+        .map(|task| (task.id.to_string(), task.title.clone()))
+        .collect())
+}
+
+// "+tag" or "-tag" for every tag in use, with the number of tasks that have it.
+fn tags(conn: &Connection, current: &str) -> Result<Candidates, CliError> {
+    let sign = &current[..1];
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for task in get_tasks_or_empty(conn)? {
+        for tag in task.tags {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+    Ok(counts
+        .into_iter()
+        .map(|(tag, count)| {
+            let plural = if count == 1 { "" } else { "s" };
+            (
+                format!("{}{}", sign, tag),
+                format!("tag, {} task{}", count, plural),
+            )
+        })
+        .collect())
+}
+
+// "project:Home.Garden", "priority:H" and "state:started".
+fn attribute_values(conn: &Connection, current: &str) -> Result<Candidates, CliError> {
+    let (key, _) = current.split_once(':').unwrap_or_default();
+    let with_key = |values: Candidates| {
+        values
+            .into_iter()
+            .map(|(value, about)| (format!("{}:{}", key, value), about))
+            .collect()
+    };
+    Ok(match key.to_lowercase().as_str() {
+        "project" | "pro" => with_key(projects(conn)?),
+        "priority" | "pri" => with_key(values(&[("H", "high"), ("M", "medium"), ("L", "low")])),
+        "state" => with_key(values(&[
+            ("pending", "state"),
+            ("started", "state"),
+            ("finished", "state"),
+            ("blocked", "state"),
+            ("someday", "state"),
+            ("cancelled", "state"),
+            ("paused", "state"),
+        ])),
+        _ => Vec::new(),
+    })
+}
+
+fn projects(conn: &Connection) -> Result<Candidates, CliError> {
+    let tasks = get_tasks_or_empty(conn)?;
+    let by_uuid: HashMap<&str, &Task> = tasks
+        .iter()
+        .map(|task| (task.uuid.as_str(), task))
+        .collect();
+    let mut paths: Vec<String> = tasks
+        .iter()
+        .filter(|task| task.project)
+        .map(|task| match task.project_path(&by_uuid) {
+            Some(path) => format!("{}.{}", path, task.title),
+            None => task.title.clone(),
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+    Ok(paths
+        .into_iter()
+        .map(|path| (path, "project".to_string()))
+        .collect())
+}
+
+fn workspaces() -> Result<Candidates, CliError> {
+    Ok(workspace_names()?
+        .into_iter()
+        .map(|name| (name, "workspace".to_string()))
+        .collect())
+}
+
+fn config_keys() -> Result<Candidates, CliError> {
+    Ok(list_settings(false)?
+        .lines()
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect())
+}
+
+#[cfg(test)]
+#[test]
+fn test_complete() {
+    use crate::crud::create_task;
+    use crate::database::create_table;
+    use crate::parser::Modification;
+
+    crate::config::use_test_config();
+    let conn = Connection::open_in_memory().unwrap();
+    create_table(&conn).unwrap();
+    let home = [
+        Modification::Tag("home".to_string(), true),
+        Modification::Project(Some("Home.Garden".to_string())),
+    ];
+    create_task(&conn, "Paint the fence".to_string(), &home).unwrap();
+    create_task(&conn, "Call Bob".to_string(), &[]).unwrap();
+    let complete = |line: &str| {
+        let words: Vec<String> = line.split(' ').map(String::from).collect();
+        complete(&conn, &words).unwrap()
+    };
+
+    assert!(complete("fin").starts_with("finish\tCheck off a task"));
+    assert_eq!(complete("stand"), "standup\tAlias: show ended:yesterday; s");
+    let finish = complete("finish ");
+    assert!(finish.contains("\tPaint the fence") && finish.contains("\tCall Bob"));
+    assert_eq!(complete("f "), finish);
+    assert_eq!(complete("+h"), "+home\ttag, 1 task");
+    assert_eq!(complete("3 modify -"), "-home\ttag, 1 task");
+    assert_eq!(
+        complete("list project:Ho"),
+        "project:Home\tproject\nproject:Home.Garden\tproject"
+    );
+    assert_eq!(
+        complete("export --format t"),
+        "taskwarrior\tformat\ntodo-txt\tformat"
+    );
+    assert_eq!(
+        complete("export --columns=id,ti"),
+        "--columns=id,title\tcolumn"
+    );
+    assert_eq!(complete("show --a"), "--archived\tshow");
+    assert_eq!(
+        complete("--color always sync "),
+        "caldav\tSync with the CalDAV task list"
+    );
+    assert_eq!(complete("import "), "");
+    assert!(completion_script(Shell::Bash).contains("pct __complete"));
+}
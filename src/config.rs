@@ -1,87 +1,668 @@
 // config.rs
 
+use crate::alias::Alias;
 use crate::error::ConfigError;
+use crate::report::{Column, SortKey};
 use serde::{Deserialize, Serialize};
-use std::{env, fs, path::PathBuf};
-use toml;
-use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::ops::Range;
+use std::{env, fs, path::Path, path::PathBuf};
+use toml_edit::{DocumentMut, Item, TableLike};
+use once_cell::sync::{Lazy, OnceCell};
+use crate::color;
 use crate::config;
+use crate::filter::parse_duration;
+use crate::font;
+use crate::parser::COMMANDS;
+use crate::paths;
+use crate::theme::Theme;
 
-pub static CONFIG: Lazy<Config> = Lazy::new(|| config::Config::initialize());
+pub static CONFIG: Lazy<Config> = Lazy::new(|| LOADED.0.clone());
 
-#[derive(Serialize, Deserialize)]
+// The merged config and where each of its settings came from
+static LOADED: Lazy<(Config, Origins)> = Lazy::new(config::Config::initialize);
+static CLI_OVERRIDES: OnceCell<Vec<String>> = OnceCell::new();
+static READ_ONLY: OnceCell<bool> = OnceCell::new();
+
+// Setting ("backup.keep_daily") -> layer it came from ("/etc/pocato/config.toml", "$POCATO_THEME")
+type Origins = BTreeMap<String, String>;
+
+// Environment variables that are paths rather than settings:
+const RESERVED_VARIABLES: [&str; 2] = ["POCATO_DIR", "POCATO_DATA_DIR"];
+
+// What a .pocato.toml may set. It comes with the repository, so it must not point the sync,
+// the database or the backups somewhere else:
+const PROJECT_KEYS: [&str; 3] = ["workspace", "default_project", "report"];
+
+// Unknown keys are errors, so typos don't go unnoticed.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    #[serde(default)]
     pub nerd_font: bool,
+    pub caldav: Option<CalDavConfig>,
+    pub backup: Option<BackupConfig>,
+    /// Archive finished and cancelled tasks after this long, e.g. "14d"
+    pub auto_archive: Option<String>,
+    /// Active workspace, $POCATO_WORKSPACE overrides it
+    pub workspace: Option<String>,
+    /// Ask before deleting tasks or emptying the trash (default true), --yes answers for a run
+    pub confirm_destructive: Option<bool>,
+    /// Project path like "Work.Website" that `pct add` puts new tasks into
+    pub default_project: Option<String>,
+    /// Folder for the task databases and backups, $POCATO_DATA_DIR overrides it
+    pub database_path: Option<PathBuf>,
+    /// Named reports, `[report.today]` is run with `pct today`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub report: BTreeMap<String, ReportConfig>,
+    /// Other names for command lines, `f = "finish"`, or lists of them run in a row (macros)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub alias: BTreeMap<String, Alias>,
+    /// dark (default), light, solarized, monochrome or one of [themes]
+    pub theme: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub themes: BTreeMap<String, ThemeConfig>,
 }
 
-impl Default for Config {
-    fn default() -> Self {
-        Config { nerd_font: false }
-    }
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalDavConfig {
+    /// URL of the CalDAV task list (collection), e.g. "https://dav.example.com/user/tasks/"
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackupConfig {
+    /// Number of daily backups to keep, 0 turns automatic backups off
+    pub keep_daily: usize,
+    /// Defaults to the "backups/daily" folder next to tasks.db
+    pub dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReportConfig {
+    /// Filter like in `pct show --filter`, e.g. "due:today or state:started"
+    pub filter: Option<String>,
+    /// Defaults to state, title and id
+    pub columns: Option<Vec<Column>>,
+    /// e.g. ["urgency-", "id+"]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sort: Vec<SortKey>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// Built-in theme to start from, "dark" by default
+    pub base: Option<String>,
+    /// Color of table headers, e.g. "bright blue", "#268bd2" or "none"
+    pub header: Option<String>,
+    /// Color of search matches, e.g. "yellow" or "none" for bold only
+    pub highlight: Option<String>,
+    /// Per task state, e.g. started = { icon = ">", color = "yellow" }
+    #[serde(default)]
+    pub states: BTreeMap<String, StateStyleConfig>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StateStyleConfig {
+    pub icon: Option<String>,
+    pub color: Option<String>,
+}
+
+// Layered configuration ___________________________________________________________________________
+//
+// Settings are read from these layers, later ones win:
+//   /etc/pocato/config.toml  >  ~/.config/pocato/config.toml  >  .pocato.toml in the current or a
+//   parent folder  >  $POCATO_<KEY> (e.g. $POCATO_THEME, $POCATO_BACKUP__KEEP_DAILY)  >  --config
+// Tables are merged key by key, so a layer can change a single setting of [backup].
+struct Layer {
+    origin: String,
+    settings: toml::Table,
+}
+
+// `--config key=value` from the command line, call before anything reads CONFIG.
+pub fn set_cli_overrides(overrides: Vec<String>) {
+    let _ = CLI_OVERRIDES.set(overrides);
+}
+
+// Commands that only look at the config, like `pct config validate`, don't create config.toml on
+// the first run. Call before anything reads CONFIG.
+pub fn set_read_only(read_only: bool) {
+    let _ = READ_ONLY.set(read_only);
 }
 
 impl Config {
-    pub fn initialize() -> Self {
-        match Config::config_handler() {
-            Ok(config) => config,
+    pub fn initialize() -> (Self, Origins) {
+        match Config::load() {
+            Ok(loaded) => loaded,
             Err(error) => {
-                eprintln!("{error}");
-                Config::default()
+                eprintln!("{}", color::stderr(error));
+                (Config::default(), Origins::new())
             }
         }
     }
 
-    fn config_handler() -> Result<Self, ConfigError> {
-        let config_dir = Config::get_config_dir()?;
-        let mut config_file = config_dir.clone();
-        config_file.push("config.toml");
+    fn load() -> Result<(Self, Origins), ConfigError> {
+        let mut merged = toml::Table::new();
+        let mut origins = Origins::new();
+        for layer in layers()? {
+            merge(&mut merged, layer.settings, "", &layer.origin, &mut origins);
+        }
+        let config = Config::deserialize(toml::Value::Table(merged))
+            .map_err(|err| ConfigError::Invalid(err.message().to_string()))?;
+        Ok((config, origins))
+    }
+
+    // Change (or with None remove) a single setting like "backup.keep_daily" in config.toml,
+    // keeping the rest of the file including comments as it is.
+    pub fn set_value(key: &str, value: Option<toml_edit::Value>) -> Result<(), ConfigError> {
+        let config_file = config_file()?;
+        let toml_config = fs::read_to_string(&config_file).unwrap_or_default();
+
+        let mut document = parse_document(&config_file, &toml_config)?;
+        insert_value(document.as_table_mut(), &split_key(key)?, value)?;
+        fs::write(config_file, document.to_string())?;
+        Ok(())
+    }
 
-        match (fs::metadata(&config_dir), fs::metadata(&config_file)) {
-            // config exists:
-            (Ok(_), Ok(_)) => return Config::parse_config(&config_file),
-            // only config.toml missing:
-            (Ok(_), Err(_)) => return Config::create_config(None, &config_file),
-            // pocato dir & config.toml missing:
-            (Err(_), Err(_)) => return Config::create_config(Some(&config_dir), &config_file),
-            // config.toml can't exist without the containing folder
-            (Err(_), Ok(_)) => unreachable!(),
+    // Detect the font again and store the result, for `pct config detect-font`.
+    pub fn detect_font() -> Result<String, ConfigError> {
+        let detection = font::detect_nerd_font(true);
+        Config::set_value("nerd_font", Some(detection.nerd_font.into()))?;
+        Ok(format!(
+            "Nerd Font icons are {} now, as {}",
+            if detection.nerd_font { "on" } else { "off" },
+            detection.reason
+        ))
+    }
+
+    // The user's config.toml, which is created on the first run.
+    fn user_config_file() -> Result<PathBuf, ConfigError> {
+        let config_dir = paths::config_dir()?;
+        let config_file = config_dir.join("config.toml");
+        if !config_file.is_file() {
+            fs::create_dir_all(&config_dir)?;
+            Config::create_config(&config_file)?;
         }
+        Ok(config_file)
     }
 
-    fn get_config_dir() -> Result<PathBuf, ConfigError> {
-        // Check if custom config directory is set with $POCATO_DIR
-        if let Ok(pocato_dir) = env::var("POCATO_DIR") {
-            let config_dir = PathBuf::from(pocato_dir);
-            return Ok(config_dir);
+    fn create_config(file: &PathBuf) -> Result<(), ConfigError> {
+        // Pick the icons on the first run, `pct config detect-font` does it again later:
+        let detection = font::detect_nerd_font(true);
+        let note = format!(
+            "\x1b[33mNerd Font icons are {} as {}, change it with: pct config detect-font\x1b[0m",
+            if detection.nerd_font { "on" } else { "off" },
+            detection.reason
+        );
+        eprintln!("{}", color::stderr(note));
+        let config = Config {
+            nerd_font: detection.nerd_font,
+            ..Config::default()
+        };
+        let toml_config = toml::to_string_pretty(&config)?;
+        fs::write(file, toml_config)?;
+        Ok(())
+    }
+}
+
+// Config commands _________________________________________________________________________________
+
+// Every setting in effect as "key = value", one per line, optionally after the layer it came from.
+pub fn list_settings(show_origin: bool) -> Result<String, ConfigError> {
+    let mut lines = Vec::new();
+    flatten("", &toml::Value::try_from(&*CONFIG)?, &mut lines);
+    if !show_origin {
+        return Ok(lines.join("\n"));
+    }
+    let origins: Vec<&str> = lines
+        .iter()
+        .map(|line| line.split(" = ").next().unwrap_or_default())
+        .map(|key| LOADED.1.get(key).map_or("default", String::as_str))
+        .collect();
+    let width = origins.iter().map(|origin| origin.chars().count()).max().unwrap_or_default();
+    let lines: Vec<String> = origins
+        .iter()
+        .zip(lines)
+        .map(|(origin, line)| format!("\x1b[34m{:<width$}\x1b[0m  {}", origin, line))
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+// The layer after config.toml that sets the key, which a change to config.toml doesn't override.
+pub fn overriding_layer(key: &str) -> Option<String> {
+    let origin = LOADED.1.get(key)?;
+    let config_files = [paths::system_config_file(), config_file().ok()];
+    let mut below = config_files.into_iter().flatten();
+    match below.any(|file| file.display().to_string() == *origin) {
+        true => None,
+        false => Some(origin.clone()),
+    }
+}
+
+pub fn get_setting(key: &str) -> Result<String, ConfigError> {
+    let mut value = toml::Value::try_from(&*CONFIG)?;
+    for part in split_key(key)? {
+        value = match value.get(part) {
+            Some(value) => value.clone(),
+            None => return Err(ConfigError::NotSet(format!("'{}' is not set", key))),
+        };
+    }
+    match value {
+        toml::Value::String(text) if !is_secret(key) => Ok(text),
+        toml::Value::Table(_) => {
+            let mut lines = Vec::new();
+            flatten(key, &value, &mut lines);
+            Ok(lines.join("\n"))
+        }
+        value => Ok(display_value(key, &value)),
+    }
+}
+
+// The value is read as TOML (true, 7, ["id", "title"] ...) and otherwise taken as text. The file is
+// only written if the result is a valid config.
+pub fn set_setting(key: &str, value: &str) -> Result<String, ConfigError> {
+    let config_file = config_file()?;
+    let toml_config = fs::read_to_string(&config_file).unwrap_or_default();
+    let document = parse_document(&config_file, &toml_config)?;
+    let parts = split_key(key)?;
+
+    let typed = value.parse::<toml_edit::Value>().ok();
+    let mut first_error = None;
+    for value in typed.into_iter().chain([value.into()]) {
+        let mut changed = document.clone();
+        insert_value(changed.as_table_mut(), &parts, Some(value.clone()))?;
+        let changed = changed.to_string();
+        match validate(&config_file, &changed) {
+            Ok(_) => {
+                fs::write(&config_file, changed)?;
+                return Ok(format!("{} = {}", key, value.decorated("", "")));
+            }
+            Err(err) => {
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    Err(first_error.expect("There is at least one value to try"))
+}
+
+// Every config file on its own, then all layers together.
+pub fn validate_config() -> Result<String, ConfigError> {
+    let files = config_files()?;
+    for file in &files {
+        read_layer(file)?;
+    }
+    let (config, _) = Config::load()?;
+    check_values(Path::new("merged config"), &config)?;
+    if files.is_empty() {
+        return Ok("Valid: there are no config files, the defaults are used".to_string());
+    }
+    let files: Vec<String> = files.iter().map(|file| file.display().to_string()).collect();
+    Ok(format!("Valid: {}", files.join(", ")))
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn config_file() -> Result<PathBuf, ConfigError> {
+    Config::user_config_file()
+}
+
+// Types and unknown keys are checked by serde, values that need more than that here.
+fn validate(file: &Path, toml_config: &str) -> Result<Config, ConfigError> {
+    parse_document(file, toml_config)?;
+    let config = deserialize(file, toml_config)?;
+    check_values(file, &config)?;
+    Ok(config)
+}
+
+fn check_values(file: &Path, config: &Config) -> Result<(), ConfigError> {
+    if let Err(ConfigError::InvalidTheme(message)) = Theme::from_config(config) {
+        return Err(ConfigError::Invalid(format!("{}: {}", file.display(), message)));
+    }
+    // An alias can't hide a command or report, it would never run:
+    for (name, alias) in &config.alias {
+        let message = if COMMANDS.iter().any(|(command, _, _)| command == name) {
+            format!("alias '{}' hides the command '{}', please rename it", name, name)
+        } else if config.report.contains_key(name) {
+            format!("alias '{}' hides the report '{}', please rename it", name, name)
+        } else if alias.command_lines().iter().all(|line| line.trim().is_empty()) {
+            format!("alias '{}' is empty", name)
         } else {
-            // Linux and MacOS config directory
-            #[cfg(not(target_os = "windows"))]
-            let config_home = format!("{}/.config", env::var("HOME")?);
+            continue;
+        };
+        return Err(ConfigError::Invalid(format!("{}: {}", file.display(), message)));
+    }
+    if let Some(older_than) = config.auto_archive.as_deref() {
+        if parse_duration(older_than).is_none() {
+            return Err(ConfigError::Invalid(format!(
+                "{}: auto_archive '{}' is no duration, use e.g. \"14d\"",
+                file.display(),
+                older_than
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn config_files() -> Result<Vec<PathBuf>, ConfigError> {
+    let user_config_file = match READ_ONLY.get() {
+        Some(true) => paths::config_dir()?.join("config.toml"),
+        _ => Config::user_config_file()?,
+    };
+    let files = [paths::system_config_file(), Some(user_config_file), project_config_file()];
+    Ok(files.into_iter().flatten().filter(|file| file.is_file()).collect())
+}
 
-            // Windows config directory
-            #[cfg(target_os = "windows")]
-            let config_home = env::var("APPDATA")?;
+// The closest .pocato.toml, so a repository can pin e.g. its workspace and default project.
+fn project_config_file() -> Option<PathBuf> {
+    let current_dir = env::current_dir().ok()?;
+    let mut files = current_dir.ancestors().map(|dir| dir.join(".pocato.toml"));
+    files.find(|file| file.is_file())
+}
+
+fn layers() -> Result<Vec<Layer>, ConfigError> {
+    let mut layers = Vec::new();
+    for file in config_files()? {
+        layers.push(Layer {
+            origin: file.display().to_string(),
+            settings: read_layer(&file)?,
+        });
+    }
 
-            let config_dir = PathBuf::from(format!("{}/pocato", config_home));
-            return Ok(config_dir);
+    let mut variables: Vec<(String, String)> = env::vars()
+        .filter(|(name, value)| name.starts_with("POCATO_") && !value.is_empty())
+        .filter(|(name, _)| !RESERVED_VARIABLES.contains(&name.as_str()))
+        .collect();
+    variables.sort();
+    for (name, value) in variables {
+        let key = name["POCATO_".len()..].to_lowercase().replace("__", ".");
+        // Variables that aren't settings may belong to something else and are left alone:
+        if let Ok(settings) = override_layer(&key, &value) {
+            let origin = format!("${}", name);
+            layers.push(Layer { origin, settings });
         }
     }
 
-    fn create_config(dir: Option<&PathBuf>, file: &PathBuf) -> Result<Self, ConfigError> {
-        if let Some(config_dir) = dir {
-            fs::create_dir_all(config_dir)?;
+    for key_value in CLI_OVERRIDES.get().into_iter().flatten() {
+        let (key, value) = key_value.split_once('=').ok_or_else(|| {
+            ConfigError::Invalid(format!("--config {}: please use key=value", key_value))
+        })?;
+        let settings = override_layer(key.trim(), value.trim())
+            .map_err(|err| ConfigError::Invalid(format!("--config {}: {}", key_value, err)))?;
+        let origin = "--config".to_string();
+        layers.push(Layer { origin, settings });
+    }
+    Ok(layers)
+}
+
+// A config file on its own may lack required keys that another layer has, everything else has to
+// be right already.
+fn read_layer(file: &Path) -> Result<toml::Table, ConfigError> {
+    let toml_config = fs::read_to_string(file)?;
+    parse_document(file, &toml_config)?;
+    if let Err(err) = toml::from_str::<Config>(&toml_config) {
+        if !err.message().starts_with("missing field") {
+            return Err(located_error(file, &toml_config, err.span(), err.message()));
         }
-        let config = Config::default();
-        // let toml_config = toml::to_string(&config)?;
-        let toml_config = toml::to_string_pretty(&config)?;
-        fs::File::create(file)?;
-        fs::write(file, toml_config)?;
-        Ok(config)
     }
+    let settings: toml::Table = toml::from_str(&toml_config)
+        .map_err(|err| located_error(file, &toml_config, err.span(), err.message()))?;
+    if file.file_name() == Some(OsStr::new(".pocato.toml")) {
+        if let Some(key) = settings.keys().find(|key| !PROJECT_KEYS.contains(&key.as_str())) {
+            return Err(ConfigError::Invalid(format!(
+                "{}: '{}' can't be set for a project, only {} can",
+                file.display(),
+                key,
+                PROJECT_KEYS.join(", ")
+            )));
+        }
+    }
+    Ok(settings)
+}
 
-    fn parse_config(file: &PathBuf) -> Result<Self, ConfigError> {
-        let toml_config = fs::read_to_string(file)?;
-        let config: Config = toml::from_str(&toml_config)?;
-        Ok(config)
+// A single setting from an environment variable or --config. The value is read as TOML if that
+// fits the setting ("true", "7"), otherwise as text.
+fn override_layer(key: &str, value: &str) -> Result<toml::Table, String> {
+    let parts = split_key(key).map_err(|err| err.to_string())?;
+    let typed = toml::from_str::<toml::Table>(&format!("value = {}", value))
+        .ok()
+        .and_then(|mut table| table.remove("value"));
+    let mut first_error = None;
+    for value in typed.into_iter().chain([toml::Value::String(value.to_string())]) {
+        let mut settings = toml::Table::new();
+        insert_path(&mut settings, &parts, value);
+        match Config::deserialize(toml::Value::Table(settings.clone())) {
+            Err(err) if !err.message().starts_with("missing field") => {
+                first_error.get_or_insert(err.message().to_string());
+            }
+            _ => return Ok(settings),
+        }
+    }
+    Err(first_error.unwrap_or_default())
+}
+
+fn insert_path(table: &mut toml::Table, parts: &[&str], value: toml::Value) {
+    match parts {
+        [key] => {
+            table.insert(key.to_string(), value);
+        }
+        [key, rest @ ..] => {
+            let entry = table
+                .entry(key.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if let toml::Value::Table(table) = entry {
+                insert_path(table, rest, value);
+            }
+        }
+        [] => {}
     }
 }
+
+// Merge a layer into the settings so far. Tables are merged, anything else is replaced.
+fn merge(
+    merged: &mut toml::Table,
+    settings: toml::Table,
+    prefix: &str,
+    origin: &str,
+    origins: &mut Origins,
+) {
+    for (key, value) in settings {
+        let path = match prefix.is_empty() {
+            true => key.clone(),
+            false => format!("{}.{}", prefix, key),
+        };
+        match (merged.get_mut(&key), value) {
+            (Some(toml::Value::Table(merged)), toml::Value::Table(settings)) => {
+                merge(merged, settings, &path, origin, origins);
+            }
+            (_, value) => {
+                origins.retain(|key, _| key != &path && !key.starts_with(&format!("{}.", path)));
+                let mut leaves = Vec::new();
+                flatten(&path, &value, &mut leaves);
+                for leaf in leaves {
+                    let key = leaf.split(" = ").next().unwrap_or_default().to_string();
+                    origins.insert(key, origin.to_string());
+                }
+                merged.insert(key, value);
+            }
+        }
+    }
+}
+
+fn parse_document(file: &Path, toml_config: &str) -> Result<DocumentMut, ConfigError> {
+    toml_config
+        .parse::<DocumentMut>()
+        .map_err(|err| located_error(file, toml_config, err.span(), err.message()))
+}
+
+fn deserialize(file: &Path, toml_config: &str) -> Result<Config, ConfigError> {
+    toml::from_str(toml_config)
+        .map_err(|err| located_error(file, toml_config, err.span(), err.message()))
+}
+
+// "config.toml:3:1: unknown field `nerd_fnt`" with the line and a caret under the culprit.
+fn located_error(
+    file: &Path,
+    toml_config: &str,
+    span: Option<Range<usize>>,
+    message: &str,
+) -> ConfigError {
+    let span = match span {
+        Some(span) if span.start <= toml_config.len() => span,
+        _ => return ConfigError::Invalid(format!("{}: {}", file.display(), message.trim())),
+    };
+    let before = &toml_config[..span.start];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    let line = toml_config[line_start..].lines().next().unwrap_or_default();
+    let column = before[line_start..].chars().count();
+    let length = toml_config[span.start..span.end.min(line_start + line.len()).max(span.start)]
+        .chars()
+        .count();
+    ConfigError::Invalid(format!(
+        "{}:{}:{}: {}\n  {}\n  {}{}",
+        file.display(),
+        before.matches('\n').count() + 1,
+        column + 1,
+        message.trim(),
+        line,
+        " ".repeat(column),
+        "^".repeat(length.max(1))
+    ))
+}
+
+fn split_key(key: &str) -> Result<Vec<&str>, ConfigError> {
+    let parts: Vec<&str> = key.split('.').collect();
+    match parts.iter().any(|part| part.trim().is_empty()) {
+        true => Err(ConfigError::NotSet(format!("'{}' is not a valid key", key))),
+        false => Ok(parts),
+    }
+}
+
+// Walks down to the table of the last key part, creating missing tables on the way.
+fn insert_value(
+    table: &mut dyn TableLike,
+    parts: &[&str],
+    value: Option<toml_edit::Value>,
+) -> Result<(), ConfigError> {
+    match (parts, value) {
+        ([key], Some(value)) => {
+            table.insert(key, Item::Value(value));
+            Ok(())
+        }
+        ([key], None) => {
+            table.remove(key);
+            Ok(())
+        }
+        ([key, rest @ ..], value) => {
+            let mut new_table = toml_edit::Table::new();
+            new_table.set_implicit(true);
+            match table.entry(key).or_insert(Item::Table(new_table)).as_table_like_mut() {
+                Some(table) => insert_value(table, rest, value),
+                None => Err(ConfigError::NotSet(format!("'{}' is not a table", key))),
+            }
+        }
+        ([], _) => Ok(()),
+    }
+}
+
+fn flatten(prefix: &str, value: &toml::Value, lines: &mut Vec<String>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let key = match prefix.is_empty() {
+                    true => key.clone(),
+                    false => format!("{}.{}", prefix, key),
+                };
+                flatten(&key, value, lines);
+            }
+        }
+        value => lines.push(format!("{} = {}", prefix, display_value(prefix, value))),
+    }
+}
+
+fn display_value(key: &str, value: &toml::Value) -> String {
+    match is_secret(key) {
+        true => "\"********\"".to_string(),
+        false => value.to_string(),
+    }
+}
+
+// Passwords are never printed:
+fn is_secret(key: &str) -> bool {
+    key.ends_with("password")
+}
+
+// Tests share a throwaway config with Nerd Font icons and forced colors, so their output doesn't
+// depend on the local config.toml or on whether stdout is a terminal. Call it before anything
+// reads CONFIG.
+#[cfg(test)]
+pub fn use_test_config() {
+    static ONCE: std::sync::Once = std::sync::Once::new();
+    ONCE.call_once(|| {
+        let config_dir = env::temp_dir().join(format!("pocato-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&config_dir).expect("Failed to create test config directory");
+        let config = "nerd_font = true\n\n\
+            [report.started]\n\
+            filter = \"state:started\"\n\
+            columns = [\"id\", \"title\", \"urgency\"]\n\
+            sort = [\"urgency-\", \"id+\"]\n\n\
+            [alias]\n\
+            f = \"finish\"\n\
+            s = \"show state:started\"\n\
+            standup = [\"show ended:yesterday\", \"s\"]\n\
+            loop = \"again\"\n\
+            again = \"loop\"\n";
+        fs::write(config_dir.join("config.toml"), config)
+            .expect("Failed to write test config");
+        env::set_var("POCATO_DIR", &config_dir);
+        colored::control::set_override(true);
+    });
+}
+
+#[cfg(test)]
+#[test]
+fn test_validate() {
+    let file = Path::new("config.toml");
+    let toml_config = "# Icons\nnerd_font = true # Hack NF\n\n[backup]\nkeep_daly = 7\n";
+    let error = validate(file, toml_config).err().unwrap().to_string();
+    assert!(error.contains("config.toml:5:1: unknown field `keep_daly`"));
+    assert!(error.ends_with("\n  keep_daly = 7\n  ^^^^^^^^^"));
+    assert!(validate(file, "theme = \"nope\"").is_err());
+    assert!(validate(file, "auto_archive = \"soon\"").is_err());
+    let error = validate(file, "[alias]\nshow = \"list\"").err().unwrap().to_string();
+    assert!(error.contains("alias 'show' hides the command 'show'"));
+    assert!(validate(file, "[alias]\nf = \"finish\"\nday = [\"list\", \"trash\"]").is_ok());
+
+    // Comments stay where they are:
+    let mut document = parse_document(file, toml_config).unwrap();
+    let parts = split_key("backup.keep_daly").unwrap();
+    insert_value(document.as_table_mut(), &parts, None).unwrap();
+    insert_value(document.as_table_mut(), &["backup", "keep_daily"], Some(7.into())).unwrap();
+    let toml_config = document.to_string();
+    assert_eq!(toml_config, "# Icons\nnerd_font = true # Hack NF\n\n[backup]\nkeep_daily = 7\n");
+    assert!(validate(file, &toml_config).is_ok());
+
+    // Layers change single settings and remember where they came from:
+    let mut merged = toml::Table::new();
+    let mut origins = Origins::new();
+    let system = toml::from_str("theme = \"light\"\n[backup]\nkeep_daily = 7").unwrap();
+    merge(&mut merged, system, "", "/etc/pocato/config.toml", &mut origins);
+    let project = override_layer("backup.dir", "/srv/backups").unwrap();
+    merge(&mut merged, project, "", ".pocato.toml", &mut origins);
+    let env = override_layer("backup.keep_daily", "3").unwrap();
+    merge(&mut merged, env, "", "$POCATO_BACKUP__KEEP_DAILY", &mut origins);
+    let config = Config::deserialize(toml::Value::Table(merged)).unwrap();
+    let backup = config.backup.unwrap();
+    assert_eq!((backup.keep_daily, config.theme.as_deref()), (3, Some("light")));
+    assert_eq!(backup.dir, Some(PathBuf::from("/srv/backups")));
+    assert_eq!(origins["backup.keep_daily"], "$POCATO_BACKUP__KEEP_DAILY");
+    assert_eq!(origins["theme"], "/etc/pocato/config.toml");
+    assert!(override_layer("nerd_font", "maybe").is_err());
+    assert!(override_layer("colour", "always").is_err());
+}
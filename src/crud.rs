@@ -1,57 +1,118 @@
 // crud.rs
 
-use dialoguer::Confirm;
-use rusqlite::Connection;
+use chrono::prelude::*;
+use dialoguer::Editor;
+use rusqlite::{Connection, OptionalExtension, Row};
+use std::collections::HashMap;
 use tabled::{
-    settings::{object::Columns, Disable, Style},
-    Table,};
+    builder::Builder,
+    settings::{object::Columns, Modify, Style, Width},
+};
 
+use crate::config::CONFIG;
 use crate::error::CrudError;
+use crate::filter::Filter;
+use crate::parser::Modification;
+use crate::prompt;
+use crate::report::{self, Column, SortKey, TABLE_COLUMNS};
 use crate::task::*;
+use crate::theme::THEME;
 
 // CRUD methods (Create, Read, Update, Delete) -----------------------------------------------------
-pub fn create_task(conn: &Connection, title: String) -> Result<String, CrudError> {
-    let id: i64 = conn.query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM tasks", [], |row| {
-        row.get(0)
-    })?;
-
-    let task = Task::new(title.clone(), id);
-
-    conn.execute(
-        "INSERT INTO tasks
-        (uuid, id, title, state, created, project, parent)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-        (
-            task.uuid,
-            task.id,
-            task.title.clone(),
-            task.state.to_string(),
-            task.created,
-            task.project,
-            task.parent,
-        ),
-    )?;
+// Add a task with tags, a priority, due date or project ("project:Work.Website"). Without a project
+// it goes into the default_project from the config, if there is one.
+pub fn create_task(
+    conn: &Connection,
+    title: String,
+    modifications: &[Modification],
+) -> Result<String, CrudError> {
+    let default_project = CONFIG
+        .default_project
+        .clone()
+        .map(|project| Modification::Project(Some(project)));
+    let modifications: Vec<&Modification> = default_project.iter().chain(modifications).collect();
+    let mut task = Task::new(title, 0);
+    // Project tasks are created first, so they don't take the task's id:
+    apply_modifications(conn, &mut task, &modifications)?;
+    task.id = next_id(conn)?;
+    insert_task(conn, &task)?;
+    let project = modifications.iter().rev().find_map(|modification| match modification {
+        Modification::Project(project) => Some(project.as_deref()),
+        _ => None,
+    });
+    let project = match project.flatten() {
+        Some(project) => format!(" to {}", project),
+        None => String::new(),
+    };
     Ok(format!(
-        "Added new task:\n  \x1b[1;34m{}\x1b[0m (#{})",
-        task.title, task.id
+        "Added new task{}:\n  \x1b[1;34m{}\x1b[0m (#{})",
+        project, task.title, task.id
     ))
 }
 
+// All tasks as a table, or a single task as a card with all its details.
 pub fn read_task(conn: &Connection, task_id: Option<i64>) -> Result<String, CrudError> {
-    let task_vec = get_tasks(conn, task_id)?;
+    let mut task_vec = get_tasks(conn, task_id)?;
+    if task_id.is_some() {
+        let task = task_vec.remove(0);
+        let all: Vec<Task> = get_tasks(conn, None)?
+            .into_iter()
+            .chain(get_archived_tasks(conn)?)
+            .collect();
+        let annotations = get_annotations(conn, &task.uuid)?;
+        let history = get_state_history(conn, &task.uuid)?;
+        return Ok(build_task_card(&task, &all, &annotations, &history));
+    }
     let task_table = build_task_table(task_vec);
     Ok(task_table)
 }
 
+// Active or archived tasks that match a filter:
+pub fn read_tasks(conn: &Connection, filter: &Filter, archived: bool) -> Result<String, CrudError> {
+    let active = get_tasks_or_empty(conn)?;
+    let tasks = match archived {
+        true => {
+            let archived = get_archived_tasks(conn)?;
+            let all: Vec<Task> = active.into_iter().chain(archived.iter().cloned()).collect();
+            filter.apply(&archived, &all)
+        }
+        false => filter.apply(&active, &active),
+    };
+
+    match (tasks.is_empty(), archived) {
+        (true, _) => Err(CrudError::TaskNotFound("Task not found".to_string())),
+        (false, true) => Ok(build_uuid_table(tasks, "Archived", Task::archived_utc)),
+        (false, false) => Ok(build_task_table(tasks)),
+    }
+}
+
+// Named report from config.toml: matching active tasks with the report's columns and sort order.
+pub fn read_report(
+    conn: &Connection,
+    filter: &Filter,
+    columns: &[Column],
+    sort: &[SortKey],
+) -> Result<String, CrudError> {
+    let all = get_tasks_or_empty(conn)?;
+    let mut tasks = filter.apply(&all, &all);
+    // Nothing due today is a valid answer, not an error:
+    if tasks.is_empty() {
+        return Ok("No tasks in this report".to_string());
+    }
+    report::sort_tasks(&mut tasks, &all, sort);
+    Ok(report::to_table(&tasks, &all, columns))
+}
+
 pub fn update_task(
     conn: &Connection,
     task_id: i64,
     task_state: TaskState,
 ) -> Result<String, CrudError> {
     let task = &get_tasks(conn, Some(task_id))?[0];
+    let ended = task_state.is_closed().then(|| chrono::Utc::now().to_string());
     conn.execute(
-        "UPDATE tasks SET state = ? WHERE id = ?",
-        (task_state.to_string(), task_id),
+        "UPDATE tasks SET state = ?, ended = ? WHERE id = ?",
+        (task_state.to_string(), ended, task_id),
     )?;
     Ok(format!(
         "{}:\n{}  \x1b[1;34m{}\x1b[0m (#{})",
@@ -62,60 +123,518 @@ pub fn update_task(
     ))
 }
 
+// Change the title, tags, priority, due date or project of a task.
+pub fn modify_task(
+    conn: &Connection,
+    task_id: i64,
+    modifications: &[Modification],
+) -> Result<String, CrudError> {
+    let mut task = get_tasks(conn, Some(task_id))?.remove(0);
+    let modifications: Vec<&Modification> = modifications.iter().collect();
+    apply_modifications(conn, &mut task, &modifications)?;
+    upsert_task(conn, &task)?;
+    Ok(format!(
+        "Modified:\n{}  \x1b[1;34m{}\x1b[0m (#{})",
+        task.state.get_icon(),
+        task.title,
+        task.id
+    ))
+}
+
+// Ask once before deleting the tasks with these ids.
+pub fn confirm_delete(conn: &Connection, task_ids: &[i64]) -> Result<(), CrudError> {
+    let question = match task_ids {
+        [task_id] => {
+            let task = &get_tasks(conn, Some(*task_id))?[0];
+            format!("Delete task {} '{}'?", task.id, task.title)
+        }
+        task_ids => format!("Delete {}?", count_tasks(task_ids.len())),
+    };
+    Ok(prompt::confirm(&question)?)
+}
+
+// Move a task to the trash, see confirm_delete for asking first.
 pub fn delete_task(conn: &Connection, task_id: i64) -> Result<String, CrudError> {
     let task = &get_tasks(conn, Some(task_id))?[0];
-    let confirmation_message = format!("Delete task {} '{}'? (yes/no)", task.id, task.title);
-    match Confirm::new().with_prompt(confirmation_message).interact() {
-        Ok(x) => {
-            if x {
-                // Delete task:
-                conn.execute("DELETE FROM tasks WHERE id = ?", [task_id])?;
-                // Renumber id numbers to close gaps created by a deletion:
-                conn.execute(
-            "UPDATE tasks SET id = (SELECT COUNT(*) FROM tasks t WHERE t.id < tasks.id) + 1",
-            [],
+    trash_task(conn, task_id)?;
+    Ok(format!(
+        "Deleted:\n\x1b[34m{}\x1b[0m (#{})",
+        task.title, task.id
+    ))
+}
+
+// Notes & annotations -----------------------------------------------------------------------------
+
+// Open the notes of a task in $VISUAL or $EDITOR, emptied notes are removed.
+pub fn edit_notes(conn: &Connection, task_id: i64) -> Result<String, CrudError> {
+    let task = &get_tasks(conn, Some(task_id))?[0];
+    let notes = task.notes.clone().unwrap_or_default();
+    let edited = match Editor::new().extension(".md").edit(&notes)? {
+        Some(edited) if edited.trim_end() != notes => edited,
+        _ => return Ok("Notes not changed".to_string()),
+    };
+    let edited = Some(edited.trim_end().to_string()).filter(|edited| !edited.is_empty());
+    conn.execute(
+        "UPDATE tasks SET notes = ? WHERE uuid = ?",
+        (edited, task.uuid.clone()),
     )?;
-                return Ok(format!(
-                    "Deleted:\n\x1b[34m{}\x1b[0m (#{})",
-                    task.title, task.id
-                ));
+    Ok(format!(
+        "Updated notes:\n{}  \x1b[1;34m{}\x1b[0m (#{})",
+        task.state.get_icon(),
+        task.title,
+        task.id
+    ))
+}
+
+pub fn annotate_task(conn: &Connection, task_id: i64, text: &str) -> Result<String, CrudError> {
+    let task = &get_tasks(conn, Some(task_id))?[0];
+    let annotation = Annotation {
+        entry: Utc::now().to_string(),
+        description: text.to_string(),
+    };
+    if !add_annotation(conn, &task.uuid, &annotation)? {
+        return Ok(format!("Task #{} already has this annotation", task.id));
+    }
+    Ok(format!(
+        "Annotated:\n{}  \x1b[1;34m{}\x1b[0m (#{})\n   {}",
+        task.state.get_icon(),
+        task.title,
+        task.id,
+        text
+    ))
+}
+
+// Trash -------------------------------------------------------------------------------------------
+
+// Move a task to the trash, trashed tasks have no id.
+pub fn trash_task(conn: &Connection, task_id: i64) -> Result<(), CrudError> {
+    conn.execute(
+        "UPDATE tasks SET deleted = ?, id = NULL WHERE id = ?",
+        (Utc::now().to_string(), task_id),
+    )?;
+    renumber_tasks(conn)
+}
+
+pub fn read_trash(conn: &Connection) -> Result<String, CrudError> {
+    let tasks = get_trashed_tasks(conn)?;
+    if tasks.is_empty() {
+        return Ok("The trash is empty".to_string());
+    }
+    Ok(build_uuid_table(tasks, "Deleted", Task::deleted_utc))
+}
+
+// Bring a task back from the trash, it gets the next free id.
+pub fn restore_task(conn: &Connection, uuid: &str) -> Result<String, CrudError> {
+    let matches: Vec<Task> = get_trashed_tasks(conn)?
+        .into_iter()
+        .filter(|task| !uuid.is_empty() && task.uuid.starts_with(uuid))
+        .collect();
+    let task = match matches.as_slice() {
+        [task] => task,
+        [] => {
+            return Err(CrudError::TaskNotFound(format!(
+                "No task in the trash has the uuid '{}'",
+                uuid
+            )))
+        }
+        _ => {
+            return Err(CrudError::TaskNotFound(format!(
+                "'{}' matches several tasks in the trash, please enter more of the uuid",
+                uuid
+            )))
+        }
+    };
+    if get_task_by_title(conn, &task.title)?.is_some() {
+        return Err(CrudError::TitleTaken(format!(
+            "There already is a task named '{}', rename it first",
+            task.title
+        )));
+    }
+    let id = next_id(conn)?;
+    conn.execute(
+        "UPDATE tasks SET deleted = NULL, id = ? WHERE uuid = ?",
+        (id, task.uuid.clone()),
+    )?;
+    Ok(format!(
+        "Restored:\n{}  \x1b[1;34m{}\x1b[0m (#{})",
+        task.state.get_icon(),
+        task.title,
+        id
+    ))
+}
+
+// Archive ---------------------------------------------------------------------------------------
+
+// Archive finished and cancelled tasks, optionally only those that ended before a point in time.
+// Archived tasks have no id, just like trashed ones.
+pub fn archive_tasks(
+    conn: &Connection,
+    before: Option<DateTime<Utc>>,
+) -> Result<String, CrudError> {
+    let tasks = get_tasks_or_empty(conn)?;
+    let now = Utc::now();
+    let mut archived = 0;
+    for task in tasks.iter().filter(|task| task.state.is_closed()) {
+        let ended = task.ended_utc().or_else(|| task.created_utc());
+        if let (Some(before), Some(ended)) = (before, ended) {
+            if ended >= before {
+                continue;
             }
         }
-        Err(err) => eprintln!("{}", err),
+        conn.execute(
+            "UPDATE tasks SET archived = ?, id = NULL WHERE uuid = ?",
+            (now.to_string(), &task.uuid),
+        )?;
+        archived += 1;
     }
-    Ok("Task not deleted".to_string())
+    renumber_tasks(conn)?;
+    Ok(format!("Archived {}", count_tasks(archived)))
+}
+
+// Delete trashed tasks for good, optionally only those trashed before a point in time.
+pub fn empty_trash(conn: &Connection, before: Option<DateTime<Utc>>) -> Result<String, CrudError> {
+    let tasks: Vec<Task> = get_trashed_tasks(conn)?
+        .into_iter()
+        .filter(|task| match (before, task.deleted_utc()) {
+            (Some(before), Some(deleted)) => deleted < before,
+            _ => true,
+        })
+        .collect();
+    if !tasks.is_empty() {
+        prompt::confirm(&format!(
+            "Remove {} from the trash for good?",
+            count_tasks(tasks.len())
+        ))?;
+    }
+    for task in &tasks {
+        conn.execute("DELETE FROM annotations WHERE uuid = ?", [&task.uuid])?;
+        conn.execute("DELETE FROM tasks WHERE uuid = ?", [&task.uuid])?;
+    }
+    Ok(format!("Removed {} from the trash", count_tasks(tasks.len())))
+}
+
+// Delete a task without confirmation, e.g. after it was deleted on a sync server.
+pub fn remove_task(conn: &Connection, uuid: &str) -> Result<(), CrudError> {
+    conn.execute("DELETE FROM annotations WHERE uuid = ?", [uuid])?;
+    conn.execute("DELETE FROM tasks WHERE uuid = ?", [uuid])?;
+    renumber_tasks(conn)
+}
+
+// Insert a task or, if its uuid already exists, overwrite it and take it out of the trash. Archived
+// tasks stay in the archive unless they are reopened, notes are kept if the new task has none.
+// Returns true for new tasks.
+pub fn upsert_task(conn: &Connection, task: &Task) -> Result<bool, CrudError> {
+    // Trashed tasks stay in the trash, any other task ends up in the task list unless it's
+    // archived and still closed, so its title mustn't be taken (like in restore_task):
+    let listed = conn
+        .query_row(
+            "SELECT deleted IS NULL AND (archived IS NULL OR NOT ?2) FROM tasks WHERE uuid = ?1",
+            (task.uuid.clone(), task.state.is_closed()),
+            |row| row.get(0),
+        )
+        .optional()?
+        .unwrap_or(true);
+    let taken = get_task_by_title(conn, &task.title)?.filter(|other| other.uuid != task.uuid);
+    if let (true, Some(other)) = (listed, taken) {
+        return Err(CrudError::TitleTaken(format!(
+            "There already is a task named '{}', rename it first",
+            other.title
+        )));
+    }
+
+    let updated = conn.execute(
+        "UPDATE tasks SET title = ?1, state = ?2, created = ?3, project = ?4, parent = ?5,
+        tags = ?6, due = ?7, priority = ?8, depends = ?9, ended = ?10,
+        notes = COALESCE(?13, notes),
+        archived = CASE WHEN ?12 THEN archived END,
+        id = CASE WHEN deleted IS NOT NULL OR (?12 AND archived IS NOT NULL) THEN NULL
+            ELSE COALESCE(id, (SELECT COALESCE(MAX(id), 0) + 1 FROM tasks)) END
+        WHERE uuid = ?11",
+        (
+            task.title.clone(),
+            task.state.to_string(),
+            task.created.clone(),
+            task.project,
+            task.parent.clone(),
+            task.tags.join(" "),
+            task.due.clone(),
+            task.priority.clone(),
+            task.depends.join(","),
+            task.ended.clone(),
+            task.uuid.clone(),
+            task.state.is_closed(),
+            task.notes.clone(),
+        ),
+    )?;
+    if updated > 0 {
+        return Ok(false);
+    }
+
+    let mut task = task.clone();
+    task.id = next_id(conn)?;
+    insert_task(conn, &task)?;
+    Ok(true)
+}
+
+pub fn get_annotations(conn: &Connection, uuid: &str) -> Result<Vec<Annotation>, CrudError> {
+    let mut stmt =
+        conn.prepare("SELECT entry, description FROM annotations WHERE uuid = ? ORDER BY entry")?;
+    let annotations = stmt.query_map([uuid], |row| {
+        Ok(Annotation {
+            entry: row.get(0)?,
+            description: row.get(1)?,
+        })
+    })?;
+    Ok(annotations.collect::<Result<_, _>>()?)
+}
+
+// A state and when the task entered it
+type StateChange = (TaskState, Option<DateTime<Utc>>);
+
+// Every state a task has been in, oldest first.
+fn get_state_history(conn: &Connection, uuid: &str) -> Result<Vec<StateChange>, CrudError> {
+    let mut stmt =
+        conn.prepare("SELECT state, changed FROM state_history WHERE uuid = ? ORDER BY rowid")?;
+    let history = stmt.query_map([uuid], |row| {
+        let changed: String = row.get(1)?;
+        Ok((TaskState::to_state(row.get(0)?), changed.parse().ok()))
+    })?;
+    Ok(history.collect::<Result<_, _>>()?)
+}
+
+// Add an annotation unless the task already has one with the same text (e.g. on re-imports).
+pub fn add_annotation(
+    conn: &Connection,
+    uuid: &str,
+    annotation: &Annotation,
+) -> Result<bool, CrudError> {
+    let added = conn.execute(
+        "INSERT INTO annotations (uuid, entry, description)
+        SELECT ?1, ?2, ?3 WHERE NOT EXISTS
+        (SELECT 1 FROM annotations WHERE uuid = ?1 AND description = ?3)",
+        (uuid, annotation.entry.clone(), annotation.description.clone()),
+    )?;
+    Ok(added > 0)
 }
 
 // Helper functions --------------------------------------------------------------------------------
 
+// "1 task", "2 tasks"
+fn count_tasks(count: usize) -> String {
+    match count {
+        1 => "1 task".to_string(),
+        count => format!("{} tasks", count),
+    }
+}
+
+fn apply_modifications(
+    conn: &Connection,
+    task: &mut Task,
+    modifications: &[&Modification],
+) -> Result<(), CrudError> {
+    for modification in modifications {
+        match modification {
+            Modification::Title(title) => task.title = title.clone(),
+            Modification::Tag(tag, true) if !task.tags.contains(tag) => task.tags.push(tag.clone()),
+            Modification::Tag(_, true) => (),
+            Modification::Tag(tag, false) => task.tags.retain(|task_tag| task_tag != tag),
+            Modification::Priority(priority) => task.priority = priority.clone(),
+            Modification::Due(due) => task.due = due.map(|due| due.to_string()),
+            Modification::Project(Some(project)) => {
+                task.parent = crate::exchange::resolve_project(conn, project)?
+            }
+            Modification::Project(None) => task.parent = String::new(),
+        }
+    }
+    Ok(())
+}
+
+const TASK_COLUMNS: &str = "uuid, id, title, state, created, project, parent, tags, due, priority,
+    depends, ended, deleted, archived, notes";
+
+fn insert_task(conn: &Connection, task: &Task) -> Result<(), CrudError> {
+    conn.execute(
+        &format!(
+            "INSERT INTO tasks ({})
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            TASK_COLUMNS
+        ),
+        (
+            task.uuid.clone(),
+            task.id,
+            task.title.clone(),
+            task.state.to_string(),
+            task.created.clone(),
+            task.project,
+            task.parent.clone(),
+            task.tags.join(" "),
+            task.due.clone(),
+            task.priority.clone(),
+            task.depends.join(","),
+            task.ended.clone(),
+            task.deleted.clone(),
+            task.archived.clone(),
+            task.notes.clone(),
+        ),
+    )?;
+    Ok(())
+}
+
+fn next_id(conn: &Connection) -> Result<i64, CrudError> {
+    Ok(conn.query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM tasks", [], |row| row.get(0))?)
+}
+
+// Renumber id numbers to close gaps created by a deletion, trashed tasks have no id:
+fn renumber_tasks(conn: &Connection) -> Result<(), CrudError> {
+    conn.execute(
+        "UPDATE tasks SET id = (SELECT COUNT(*) FROM tasks t WHERE t.id < tasks.id) + 1
+        WHERE id IS NOT NULL",
+        [],
+    )?;
+    Ok(())
+}
+
 fn build_task_table(tasks: Vec<Task>) -> String {
-    let style = Style::rounded();
-    let disable = Disable::column(
-        Columns::new(3..), // disable range
-                             // Columns::single(3), // disable single column
-    );
-    let table = Table::new(tasks).with(style).with(disable).to_string();
-    table
+    report::to_table(&tasks, &tasks, &TABLE_COLUMNS)
+}
+
+// Tasks without an id (trashed or archived) are listed with their uuid and when they were removed.
+fn build_uuid_table(
+    tasks: Vec<Task>,
+    date_header: &str,
+    date: fn(&Task) -> Option<DateTime<Utc>>,
+) -> String {
+    let mut builder = Builder::default();
+    builder.set_header([
+        "📝".to_string(),
+        THEME.header("Task"),
+        THEME.header("UUID"),
+        THEME.header(date_header),
+    ]);
+    for task in tasks {
+        let date = date(&task).map(local_time).unwrap_or_default();
+        builder.push_record([task.state.get_icon().to_string(), task.title, task.uuid, date]);
+    }
+    builder.build().with(Style::rounded()).to_string()
+}
+
+// One row per field, empty fields are left out. Related tasks are shown with their titles and long
+// notes are wrapped.
+fn build_task_card(
+    task: &Task,
+    all: &[Task],
+    annotations: &[Annotation],
+    history: &[StateChange],
+) -> String {
+    let now = Utc::now();
+    let by_uuid: HashMap<&str, &Task> = all.iter().map(|task| (task.uuid.as_str(), task)).collect();
+    let label = |uuid: &str| match by_uuid.get(uuid) {
+        Some(task) => task_label(task),
+        None => uuid.to_string(),
+    };
+    let when = |time: DateTime<Utc>| format!("{} ({})", local_time(time), relative_time(time, now));
+
+    let children: Vec<String> = all
+        .iter()
+        .filter(|child| child.parent == task.uuid)
+        .map(|child| format!("{} {}", child.state.get_icon(), task_label(child)))
+        .collect();
+    let annotations: Vec<String> = annotations
+        .iter()
+        .map(|annotation| {
+            let entry = annotation.entry.parse().ok().map(local_time);
+            format!("{}  {}", entry.unwrap_or_default(), annotation.description)
+        })
+        .collect();
+    let history: Vec<String> = history
+        .iter()
+        .map(|(state, changed)| {
+            let changed = changed.map(when).unwrap_or_default();
+            format!("{} {:<9}  {}", state.get_icon(), state.to_string(), changed)
+        })
+        .collect();
+    let lines = |lines: Vec<String>| Some(lines.join("\n")).filter(|lines| !lines.is_empty());
+
+    let title = match task.project {
+        true => format!("\x1b[1;34m{}\x1b[0m (project)", task.title),
+        false => format!("\x1b[1;34m{}\x1b[0m", task.title),
+    };
+    let fields = [
+        ("Task", Some(title)),
+        ("ID", (task.id > 0).then(|| task.id.to_string())),
+        (
+            "State",
+            Some(format!("{} {}", task.state.get_icon(), task.state.to_string())),
+        ),
+        ("Project", task.project_path(&by_uuid)),
+        ("Parent", Some(task.parent.as_str()).filter(|parent| !parent.is_empty()).map(label)),
+        ("Children", lines(children)),
+        ("Tags", Some(task.tags.join(" ")).filter(|tags| !tags.is_empty())),
+        ("Priority", task.priority.clone()),
+        ("Due", task.due_utc().map(when)),
+        ("Depends", lines(task.depends.iter().map(|uuid| label(uuid)).collect())),
+        ("Created", task.created_utc().map(when)),
+        ("Ended", task.ended_utc().map(when)),
+        ("UUID", Some(task.uuid.clone())),
+        ("Notes", task.notes.clone()),
+        ("Annotations", lines(annotations)),
+        ("History", lines(history)),
+    ];
+
+    let mut builder = Builder::default();
+    for (name, value) in fields {
+        if let Some(value) = value {
+            builder.push_record([THEME.header(name), value]);
+        }
+    }
+    builder
+        .build()
+        .with(Style::rounded())
+        .with(Modify::new(Columns::single(1)).with(Width::wrap(72).keep_words()))
+        .to_string()
+}
+
+// "Title (#3)", archived tasks have no id.
+fn task_label(task: &Task) -> String {
+    match task.id {
+        0 => task.title.clone(),
+        id => format!("{} (#{})", task.title, id),
+    }
+}
+
+fn local_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+}
+
+// "3 days ago", "in 2 hours" ...
+fn relative_time(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - time).num_seconds();
+    let (count, unit) = match seconds.abs() {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds.abs() / 60, "minute"),
+        3600..=86_399 => (seconds.abs() / 3600, "hour"),
+        86_400..=2_591_999 => (seconds.abs() / 86_400, "day"),
+        2_592_000..=31_535_999 => (seconds.abs() / 2_592_000, "month"),
+        _ => (seconds.abs() / 31_536_000, "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    match seconds > 0 {
+        true => format!("{} {}{} ago", count, unit, plural),
+        false => format!("in {} {}{}", count, unit, plural),
+    }
 }
 
-fn get_tasks(conn: &Connection, task_id: Option<i64>) -> Result<Vec<Task>, CrudError> {
+pub fn get_tasks(conn: &Connection, task_id: Option<i64>) -> Result<Vec<Task>, CrudError> {
     let query = match task_id {
-        Some(id) => format!("SELECT * FROM tasks WHERE id = {}", id),
-        None => "SELECT * FROM tasks".to_string(),
+        Some(id) => format!("SELECT {} FROM tasks WHERE id = {}", TASK_COLUMNS, id),
+        None => format!(
+            "SELECT {} FROM tasks WHERE deleted IS NULL AND archived IS NULL",
+            TASK_COLUMNS
+        ),
     };
 
     let mut stmt = conn.prepare(&query)?;
-    let tasks = stmt.query_map((), |row| {
-        Ok(Task {
-            uuid: row.get(0)?,
-            id: row.get(1)?,
-            title: row.get(2)?,
-            state: TaskState::to_state(row.get(3)?),
-            created: row.get(4)?,
-            project: row.get(5)?,
-            parent: row.get(6)?,
-        })
-    })?;
+    let tasks = stmt.query_map((), task_from_row)?;
 
     let mut task_vec = Vec::new();
 
@@ -129,3 +648,187 @@ fn get_tasks(conn: &Connection, task_id: Option<i64>) -> Result<Vec<Task>, CrudE
 
     Ok(task_vec)
 }
+
+// All active tasks, where an empty list isn't an error.
+pub fn get_tasks_or_empty(conn: &Connection) -> Result<Vec<Task>, CrudError> {
+    match get_tasks(conn, None) {
+        Err(CrudError::TaskNotFound(_)) => Ok(Vec::new()),
+        result => result,
+    }
+}
+
+// Tasks in the trash are only found by the trash functions:
+pub fn get_task_by_uuid(conn: &Connection, uuid: &str) -> Result<Option<Task>, CrudError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks WHERE uuid = ? AND deleted IS NULL",
+        TASK_COLUMNS
+    ))?;
+    Ok(stmt.query_row([uuid], task_from_row).optional()?)
+}
+
+pub fn get_task_by_title(conn: &Connection, title: &str) -> Result<Option<Task>, CrudError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks WHERE title = ? AND deleted IS NULL AND archived IS NULL",
+        TASK_COLUMNS
+    ))?;
+    Ok(stmt.query_row([title], task_from_row).optional()?)
+}
+
+fn get_trashed_tasks(conn: &Connection) -> Result<Vec<Task>, CrudError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks WHERE deleted IS NOT NULL ORDER BY deleted",
+        TASK_COLUMNS
+    ))?;
+    let tasks = stmt.query_map((), task_from_row)?;
+    Ok(tasks.collect::<Result<_, _>>()?)
+}
+
+fn get_archived_tasks(conn: &Connection) -> Result<Vec<Task>, CrudError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks WHERE archived IS NOT NULL AND deleted IS NULL ORDER BY archived",
+        TASK_COLUMNS
+    ))?;
+    let tasks = stmt.query_map((), task_from_row)?;
+    Ok(tasks.collect::<Result<_, _>>()?)
+}
+
+fn task_from_row(row: &Row) -> rusqlite::Result<Task> {
+    Ok(Task {
+        uuid: row.get(0)?,
+        id: row.get::<_, Option<i64>>(1)?.unwrap_or(0),
+        title: row.get(2)?,
+        state: TaskState::to_state(row.get(3)?),
+        created: row.get(4)?,
+        project: row.get(5)?,
+        parent: row.get(6)?,
+        tags: split_list(row.get(7)?, ' '),
+        due: row.get(8)?,
+        priority: row.get(9)?,
+        depends: split_list(row.get(10)?, ','),
+        ended: row.get(11)?,
+        deleted: row.get(12)?,
+        archived: row.get(13)?,
+        notes: row.get(14)?,
+    })
+}
+
+fn split_list(list: String, separator: char) -> Vec<String> {
+    list.split(separator)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn test_trash() {
+    crate::config::use_test_config();
+    let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
+    crate::database::create_table(&conn).expect("Failed to create tables");
+    create_task(&conn, "Water the plants".to_string(), &[]).unwrap();
+    create_task(&conn, "Paint the fence".to_string(), &[]).unwrap();
+
+    trash_task(&conn, 1).unwrap();
+    let tasks = get_tasks(&conn, None).unwrap();
+    assert_eq!((tasks.len(), tasks[0].id), (1, 1));
+    let trashed = get_trashed_tasks(&conn).unwrap();
+    assert_eq!(trashed[0].title, "Water the plants");
+
+    // The title is free again, so the trashed task can't come back until it's renamed:
+    create_task(&conn, "Water the plants".to_string(), &[]).unwrap();
+    assert!(matches!(
+        restore_task(&conn, &trashed[0].uuid[..8]),
+        Err(CrudError::TitleTaken(_))
+    ));
+    conn.execute("DELETE FROM tasks WHERE id = 2", []).unwrap();
+    assert!(restore_task(&conn, &trashed[0].uuid[..8]).unwrap().ends_with("(#2)"));
+
+    trash_task(&conn, 1).unwrap();
+    // Imports update trashed tasks without restoring them and can't take a listed title:
+    let mut task = get_trashed_tasks(&conn).unwrap().remove(0);
+    task.state = TaskState::Started;
+    assert!(!upsert_task(&conn, &task).unwrap());
+    assert_eq!(get_tasks(&conn, None).unwrap().len(), 1);
+    let twin = Task::new("Water the plants".to_string(), 0);
+    assert!(matches!(upsert_task(&conn, &twin), Err(CrudError::TitleTaken(_))));
+
+    assert!(matches!(empty_trash(&conn, None), Err(CrudError::PromptError(_))));
+    crate::prompt::init(crate::prompt::InputMode {
+        yes: true,
+        no_input: false,
+    });
+    let an_hour_ago = Utc::now() - chrono::Duration::hours(1);
+    assert_eq!(empty_trash(&conn, Some(an_hour_ago)).unwrap(), "Removed 0 tasks from the trash");
+    assert_eq!(empty_trash(&conn, None).unwrap(), "Removed 1 task from the trash");
+    assert_eq!(read_trash(&conn).unwrap(), "The trash is empty");
+
+    // Removed tasks, e.g. deleted on the sync server, take their annotations along:
+    let uuid = get_tasks(&conn, Some(1)).unwrap()[0].uuid.clone();
+    let annotation = Annotation {
+        entry: Utc::now().to_string(),
+        description: "Ask Bob".to_string(),
+    };
+    add_annotation(&conn, &uuid, &annotation).unwrap();
+    remove_task(&conn, &uuid).unwrap();
+    assert!(get_annotations(&conn, &uuid).unwrap().is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn test_archive() {
+    crate::config::use_test_config();
+    let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
+    crate::database::create_table(&conn).expect("Failed to create tables");
+    create_task(&conn, "Water the plants".to_string(), &[]).unwrap();
+    create_task(&conn, "Paint the fence".to_string(), &[]).unwrap();
+    update_task(&conn, 1, TaskState::Finished).unwrap();
+
+    let an_hour_ago = Utc::now() - chrono::Duration::hours(1);
+    assert_eq!(archive_tasks(&conn, Some(an_hour_ago)).unwrap(), "Archived 0 tasks");
+    assert_eq!(archive_tasks(&conn, None).unwrap(), "Archived 1 task");
+    let tasks = get_tasks(&conn, None).unwrap();
+    assert_eq!((tasks.len(), tasks[0].id), (1, 1));
+
+    let filter = Filter::parse("plants").unwrap();
+    assert!(read_tasks(&conn, &filter, true).unwrap().contains("Water the plants"));
+    assert!(read_tasks(&conn, &filter, false).is_err());
+
+    // Reopening an archived task, e.g. by an import, brings it back to the task list:
+    let mut task = get_archived_tasks(&conn).unwrap().remove(0);
+    task.state = TaskState::Pending;
+    upsert_task(&conn, &task).unwrap();
+    assert_eq!(get_tasks(&conn, None).unwrap().len(), 2);
+}
+
+#[cfg(test)]
+#[test]
+fn test_task_card() {
+    crate::config::use_test_config();
+    let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
+    crate::database::create_table(&conn).expect("Failed to create tables");
+    let mut project = Task::new("Garden".to_string(), 1);
+    project.project = true;
+    let mut task = Task::new("Plant tomatoes".to_string(), 2);
+    task.parent = project.uuid.clone();
+    task.depends = vec![project.uuid.clone()];
+    upsert_task(&conn, &project).unwrap();
+    upsert_task(&conn, &task).unwrap();
+    update_task(&conn, 2, TaskState::Started).unwrap();
+
+    let card = read_task(&conn, Some(2)).unwrap();
+    assert!(card.contains("│ Garden (#1) "));
+    assert!(!card.contains(&project.uuid));
+    assert!(card.contains("Started    ") && card.contains("(just now)"));
+    let card = read_task(&conn, Some(1)).unwrap();
+    assert!(card.contains("Plant tomatoes (#2)"));
+
+    let project = Modification::Project(Some("Garden.Spring".to_string()));
+    let added = create_task(&conn, "Buy seeds".to_string(), &[project]).unwrap();
+    assert!(added.starts_with("Added new task to Garden.Spring:"));
+    let card = read_task(&conn, Some(4)).unwrap();
+    assert!(card.contains("Buy seeds") && card.contains("Garden.Spring"));
+
+    let now = Utc::now();
+    assert_eq!(relative_time(now - chrono::Duration::days(3), now), "3 days ago");
+    assert_eq!(relative_time(now + chrono::Duration::minutes(61), now), "in 1 hour");
+}
@@ -1,48 +1,85 @@
 // database.rs
 
+use chrono::Utc;
 use rusqlite::Connection;
-use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use crate::backup::auto_backup;
+use crate::color;
+use crate::config::CONFIG;
+use crate::crud::archive_tasks;
+use crate::error::DatabaseError;
+use crate::filter::parse_duration;
+use crate::parser::Command;
+use crate::paths::data_dir;
+use crate::workspace::DEFAULT_WORKSPACE;
 
 // Setup database path _____________________________________________________________________________
-fn database_dir() -> PathBuf {
-    let database_dir = match env::consts::OS {
-        "linux" | "macos" => match dirs::data_dir() {
-            Some(xdg_data_home) => xdg_data_home.join("pocato/"),
-            None => panic!("Can't access the ~/.local/share/ folder"),
-        },
-        "windows" => match dirs::data_local_dir() {
-            Some(appdata) => appdata.join("pocato/"),
-            None => panic!("Can't find the AppData directory."),
-        },
-        _ => panic!("Unsupported platform"),
-    };
-
-    if !database_dir.is_dir() {
-        match fs::create_dir(&database_dir) {
-            Ok(_) => (),
-            Err(err) => panic!("{}", err),
-        };
+
+// The default workspace keeps its tasks in tasks.db, all others in workspaces/<name>.db:
+pub fn database_file(workspace: &str) -> Result<PathBuf, DatabaseError> {
+    match workspace {
+        DEFAULT_WORKSPACE => Ok(data_dir()?.join("tasks.db")),
+        _ => Ok(data_dir()?
+            .join("workspaces")
+            .join(format!("{}.db", workspace))),
     }
-    database_dir
 }
 
 // Initialize database _____________________________________________________________________________
-pub fn init_db() -> Connection {
-    let database_file = database_dir().join("tasks.db");
-    match Connection::open(database_file) {
-        Ok(conn) => {
-            create_table(&conn);
-            conn
+pub fn init_db(database_file: &Path) -> Result<Connection, DatabaseError> {
+    if let Some(dir) = database_file.parent() {
+        fs::create_dir_all(dir).map_err(|err| DatabaseError::Inaccessible(dir.to_path_buf(), err))?;
+    }
+    let conn = Connection::open(database_file)?;
+    create_table(&conn)?;
+    Ok(conn)
+}
+
+// Housekeeping ____________________________________________________________________________________
+//
+// Runs before each command. Completion and help never change anything, so a <TAB> neither copies
+// the database nor archives tasks.
+pub fn before_command(conn: &Connection, command: &Command) {
+    if let (Some(backup), true) = (&CONFIG.backup, command.changes_tasks()) {
+        if let Err(err) = auto_backup(conn, backup) {
+            let warning = format!("\x1b[33mAutomatic backup failed:\x1b[0m\n{}", err);
+            eprintln!("{}", color::stderr(warning));
+        }
+    }
+    // Completion, help and settings don't show tasks, archiving there would drop ids being typed:
+    let shows_tasks = !matches!(
+        command,
+        Command::Complete { .. }
+            | Command::Completions { .. }
+            | Command::Help
+            | Command::Config { .. }
+            | Command::Workspace { .. }
+    );
+    if let (Some(older_than), true) = (&CONFIG.auto_archive, shows_tasks) {
+        match parse_duration(older_than) {
+            Some(duration) => {
+                if let Err(err) = archive_tasks(conn, Some(Utc::now() - duration)) {
+                    let warning =
+                        format!("\x1b[33mAutomatic archiving failed:\x1b[0m\n{}", err);
+                    eprintln!("{}", color::stderr(warning));
+                }
+            }
+            None => eprintln!(
+                "{}",
+                color::stderr(format!(
+                    "\x1b[33mInvalid auto_archive duration '{}', use e.g. \"14d\".\x1b[0m",
+                    older_than
+                ))
+            ),
         }
-        Err(err) => panic!("Could not initialize Database: {}", err),
     }
 }
 
 // Create table ___________________________________________________________________________________""
-pub fn create_table(conn: &Connection) {
-    let result = conn.execute(
+pub fn create_table(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute(
         "CREATE TABLE IF NOT EXISTS tasks (
             uuid TEXT PRIMARY KEY, 
             id INTEGER, 
@@ -53,9 +90,163 @@ pub fn create_table(conn: &Connection) {
             parent TEXT
             )",
         (),
-    );
-    match result {
-        Ok(_) => (),
-        Err(err) => panic!("Could not create Table {}", err),
+    )?;
+
+    // Last synced state of every task that is shared with a CalDAV server:
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS caldav_sync (
+            uuid TEXT PRIMARY KEY,
+            href TEXT NOT NULL UNIQUE,
+            etag TEXT,
+            snapshot TEXT NOT NULL
+            )",
+        (),
+    )?;
+
+    migrate(conn)
+}
+
+// Migrations ______________________________________________________________________________________
+// Schema changes after the initial tables. Each entry upgrades the schema by one version, the
+// current version is stored in SQLite's user_version. Never edit an entry, append a new one.
+const MIGRATIONS: &[&str] = &[
+    // 1: Tags, due date, priority, dependencies, end date and annotations
+    "ALTER TABLE tasks ADD COLUMN tags TEXT NOT NULL DEFAULT '';
+    ALTER TABLE tasks ADD COLUMN due DATETIME;
+    ALTER TABLE tasks ADD COLUMN priority TEXT;
+    ALTER TABLE tasks ADD COLUMN depends TEXT NOT NULL DEFAULT '';
+    ALTER TABLE tasks ADD COLUMN ended DATETIME;
+    CREATE TABLE annotations (
+        uuid TEXT NOT NULL,
+        entry DATETIME NOT NULL,
+        description TEXT NOT NULL
+        );
+    CREATE INDEX annotations_uuid ON annotations (uuid);",
+    // 2: Trash. Titles only need to be unique among tasks that are not in the trash, and SQLite
+    // can't drop a constraint, so the table is rebuilt with a partial index instead.
+    "CREATE TABLE tasks_new (
+        uuid TEXT PRIMARY KEY,
+        id INTEGER,
+        title TEXT NOT NULL,
+        state INTEGER,
+        created DATETIME,
+        project BOOL,
+        parent TEXT,
+        tags TEXT NOT NULL DEFAULT '',
+        due DATETIME,
+        priority TEXT,
+        depends TEXT NOT NULL DEFAULT '',
+        ended DATETIME,
+        deleted DATETIME
+        );
+    INSERT INTO tasks_new (uuid, id, title, state, created, project, parent, tags, due, priority,
+        depends, ended)
+        SELECT uuid, id, title, state, created, project, parent, tags, due, priority, depends, ended
+        FROM tasks;
+    DROP TABLE tasks;
+    ALTER TABLE tasks_new RENAME TO tasks;
+    CREATE UNIQUE INDEX tasks_title ON tasks (title) WHERE deleted IS NULL;",
+    // 3: Archive, archived tasks don't block their title either
+    "ALTER TABLE tasks ADD COLUMN archived DATETIME;
+    DROP INDEX tasks_title;
+    CREATE UNIQUE INDEX tasks_title ON tasks (title) WHERE deleted IS NULL AND archived IS NULL;
+    CREATE INDEX tasks_active ON tasks (id) WHERE deleted IS NULL AND archived IS NULL;",
+    // 4: Full-text search over titles and annotations, kept up to date by triggers
+    "CREATE VIRTUAL TABLE tasks_fts USING fts5(
+        uuid UNINDEXED,
+        title,
+        annotations,
+        tokenize = 'unicode61 remove_diacritics 2'
+        );
+    CREATE TRIGGER tasks_fts_insert AFTER INSERT ON tasks BEGIN
+        INSERT INTO tasks_fts (uuid, title, annotations) VALUES (NEW.uuid, NEW.title,
+            (SELECT group_concat(description, char(10)) FROM annotations WHERE uuid = NEW.uuid));
+    END;
+    CREATE TRIGGER tasks_fts_update AFTER UPDATE OF title ON tasks BEGIN
+        UPDATE tasks_fts SET title = NEW.title WHERE uuid = NEW.uuid;
+    END;
+    CREATE TRIGGER tasks_fts_delete AFTER DELETE ON tasks BEGIN
+        DELETE FROM tasks_fts WHERE uuid = OLD.uuid;
+    END;
+    CREATE TRIGGER annotations_fts_insert AFTER INSERT ON annotations BEGIN
+        UPDATE tasks_fts SET annotations =
+            (SELECT group_concat(description, char(10)) FROM annotations WHERE uuid = NEW.uuid)
+            WHERE uuid = NEW.uuid;
+    END;
+    CREATE TRIGGER annotations_fts_delete AFTER DELETE ON annotations BEGIN
+        UPDATE tasks_fts SET annotations =
+            (SELECT group_concat(description, char(10)) FROM annotations WHERE uuid = OLD.uuid)
+            WHERE uuid = OLD.uuid;
+    END;
+    INSERT INTO tasks_fts (uuid, title, annotations) SELECT uuid, title,
+        (SELECT group_concat(description, char(10)) FROM annotations WHERE uuid = tasks.uuid)
+        FROM tasks;",
+    // 5: Notes, which are searchable as well. FTS5 tables can't get new columns, so it's rebuilt.
+    "ALTER TABLE tasks ADD COLUMN notes TEXT;
+    DROP TRIGGER tasks_fts_insert;
+    DROP TRIGGER tasks_fts_update;
+    DROP TABLE tasks_fts;
+    CREATE VIRTUAL TABLE tasks_fts USING fts5(
+        uuid UNINDEXED,
+        title,
+        notes,
+        annotations,
+        tokenize = 'unicode61 remove_diacritics 2'
+        );
+    CREATE TRIGGER tasks_fts_insert AFTER INSERT ON tasks BEGIN
+        INSERT INTO tasks_fts (uuid, title, notes, annotations) VALUES (NEW.uuid, NEW.title,
+            NEW.notes,
+            (SELECT group_concat(description, char(10)) FROM annotations WHERE uuid = NEW.uuid));
+    END;
+    CREATE TRIGGER tasks_fts_update AFTER UPDATE OF title, notes ON tasks BEGIN
+        UPDATE tasks_fts SET title = NEW.title, notes = NEW.notes WHERE uuid = NEW.uuid;
+    END;
+    INSERT INTO tasks_fts (uuid, title, notes, annotations) SELECT uuid, title, notes,
+        (SELECT group_concat(description, char(10)) FROM annotations WHERE uuid = tasks.uuid)
+        FROM tasks;",
+    // 6: State history. Tasks from before it start with their current state, as of when they
+    // ended or were created.
+    "CREATE TABLE state_history (
+        uuid TEXT NOT NULL,
+        state TEXT NOT NULL,
+        changed DATETIME NOT NULL
+        );
+    CREATE INDEX state_history_uuid ON state_history (uuid);
+    CREATE TRIGGER state_history_insert AFTER INSERT ON tasks BEGIN
+        INSERT INTO state_history (uuid, state, changed)
+            VALUES (NEW.uuid, NEW.state, COALESCE(NEW.ended, NEW.created));
+    END;
+    CREATE TRIGGER state_history_update AFTER UPDATE OF state ON tasks
+        WHEN NEW.state IS NOT OLD.state BEGIN
+        INSERT INTO state_history (uuid, state, changed)
+            VALUES (NEW.uuid, NEW.state, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+    END;
+    CREATE TRIGGER state_history_delete AFTER DELETE ON tasks BEGIN
+        DELETE FROM state_history WHERE uuid = OLD.uuid;
+    END;
+    INSERT INTO state_history (uuid, state, changed)
+        SELECT uuid, state, COALESCE(ended, created) FROM tasks;",
+];
+
+pub const SCHEMA_VERSION: usize = MIGRATIONS.len();
+
+fn migrate(conn: &Connection) -> Result<(), DatabaseError> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    // An older pct must not write to a database it doesn't understand:
+    if version > SCHEMA_VERSION {
+        return Err(DatabaseError::SchemaTooNew(version, SCHEMA_VERSION));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let result = conn.execute_batch(&format!(
+            "BEGIN; {} PRAGMA user_version = {}; COMMIT;",
+            migration,
+            index + 1
+        ));
+        if let Err(err) = result {
+            let _ = conn.execute_batch("ROLLBACK");
+            return Err(DatabaseError::Migration(index + 1, err));
+        }
     }
+    Ok(())
 }
@@ -3,6 +3,7 @@
 use rusqlite::Error;
 use std::env::VarError;
 use std::num::ParseIntError;
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -15,11 +16,62 @@ pub enum CliError {
     #[error("\x1b[31mInvalid Command Format: {0}\x1b[0m")]
     InvalidArgumentFormat(#[from] ParseIntError),
 
+    #[error("\x1b[31mAlias Loop: {0}\x1b[0m")]
+    AliasLoop(String),
+
     #[error(transparent)]
     CrudError(#[from] CrudError),
+
+    #[error(transparent)]
+    ExchangeError(#[from] ExchangeError),
+
+    #[error(transparent)]
+    SyncError(#[from] SyncError),
+
+    #[error(transparent)]
+    ParseError(#[from] ParseError),
+
+    #[error(transparent)]
+    BackupError(#[from] BackupError),
+
+    #[error(transparent)]
+    WorkspaceError(#[from] WorkspaceError),
+
+    #[error(transparent)]
+    DatabaseError(#[from] DatabaseError),
+
+    #[error(transparent)]
+    ConfigError(#[from] ConfigError),
+
+    #[error("\x1b[31mShell Error:\n\x1b[0m{0}")]
+    ShellError(#[from] rustyline::error::ReadlineError),
+
+    #[error(transparent)]
+    PromptError(#[from] PromptError),
+}
+
+impl CliError {
+    // 2 for command lines pct doesn't understand (like clap's own errors), 3 if a confirmation was
+    // answered with no and 4 if it couldn't be asked, so scripts can tell them apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::ParseError(_)
+            | CliError::InvalidCommandArguments(_)
+            | CliError::InvalidArgumentFormat(_)
+            | CliError::AliasLoop(_) => 2,
+            CliError::PromptError(err) | CliError::CrudError(CrudError::PromptError(err)) => {
+                match err {
+                    PromptError::Refused(_) => 3,
+                    PromptError::InputNeeded(_) => 4,
+                }
+            }
+            _ => 1,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum ConfigError {
     #[error("\x1b[33mCan't locate config folder. Using default settings.\n
         Consider setting $POCATO_DIR:\n\x1b[0m{0}")]
@@ -31,8 +83,39 @@ pub enum ConfigError {
     #[error("\x1b[31mTOML Deserialization Error:\n\x1b[0m{0}")]
     TomlSerError(#[from] toml::ser::Error),
 
+    #[error("\x1b[31mTOML Error:\n\x1b[0m{0}")]
+    TomlEditError(#[from] toml_edit::TomlError),
+
     #[error("\x1b[31m Read/Write Error:\n\x1b[0m{0}")]
     StdIOError(#[from] std::io::Error),
+
+    #[error("\x1b[33mInvalid theme, using the dark theme instead:\n\x1b[0m{0}")]
+    InvalidTheme(String),
+
+    #[error("\x1b[31mInvalid config:\n\x1b[0m{0}")]
+    Invalid(String),
+
+    #[error("\x1b[31mConfig Error:\n\x1b[0m{0}")]
+    NotSet(String),
+}
+
+#[derive(Debug, Error)]
+pub enum DatabaseError {
+    #[error("\x1b[31mNo Data Folder:\n\x1b[0m{0}")]
+    NoDataDir(String),
+
+    #[error("\x1b[31mCan't access {}:\n\x1b[0m{1}", .0.display())]
+    Inaccessible(PathBuf, std::io::Error),
+
+    #[error("\x1b[31mCan't migrate the database to version {0}:\n\x1b[0m{1}")]
+    Migration(usize, Error),
+
+    #[error("\x1b[31mDatabase Too New:\n\x1b[0mThe database has schema version {0}, this pct only \
+        knows versions up to {1}. Please update pct first.")]
+    SchemaTooNew(usize, usize),
+
+    #[error("\x1b[31mRusqlite Error:\n\x1b[0m{0}")]
+    Rusqlite(#[from] Error),
 }
 
 #[derive(Debug, Error)]
@@ -41,4 +124,158 @@ pub enum CrudError {
     DatabaseError(#[from] Error),
     #[error("\x1b[31mInput Error:\n\x1b[0m{0}")]
     TaskNotFound(String),
+    #[error("\x1b[31mInput Error:\n\x1b[0m{0}")]
+    TitleTaken(String),
+    #[error("\x1b[31mEditor Error:\n\x1b[0m{0}")]
+    EditorError(#[from] dialoguer::Error),
+    #[error(transparent)]
+    PromptError(#[from] PromptError),
+}
+
+#[derive(Debug, Error)]
+pub enum PromptError {
+    #[error("\x1b[33mCancelled:\n\x1b[0m{0}")]
+    Refused(String),
+    #[error("\x1b[31mInput Needed:\n\x1b[0m{0}")]
+    InputNeeded(String),
+}
+
+#[derive(Debug, Error)]
+pub enum ExchangeError {
+    #[error("\x1b[31mUnknown file format:\n\x1b[0m{0}")]
+    UnknownFormat(String),
+
+    #[error("\x1b[31mInvalid {0} file:\n\x1b[0m{1}")]
+    InvalidFormat(&'static str, String),
+
+    #[error("\x1b[31mRead/Write Error:\n\x1b[0m{0}")]
+    StdIOError(#[from] std::io::Error),
+
+    #[error(transparent)]
+    CrudError(#[from] CrudError),
 }
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error("\x1b[31mSync is not configured:\n\x1b[0m{0}")]
+    NotConfigured(String),
+
+    #[error("\x1b[31mSync is locked:\n\x1b[0m{0}")]
+    Locked(String),
+
+    #[error("\x1b[31mCalDAV Request Failed:\n\x1b[0m{0}")]
+    Http(Box<ureq::Error>),
+
+    #[error("\x1b[31mCalDAV Href Refused:\n\x1b[0m{0}")]
+    OtherServer(String),
+
+    #[error("\x1b[31mRead/Write Error:\n\x1b[0m{0}")]
+    StdIOError(#[from] std::io::Error),
+
+    #[error(transparent)]
+    ExchangeError(#[from] ExchangeError),
+
+    #[error(transparent)]
+    CrudError(#[from] CrudError),
+}
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("\x1b[31mInvalid Backup:\n\x1b[0m{0}")]
+    InvalidBackup(String),
+
+    #[error("\x1b[31mInvalid Backup:\n\x1b[0mThe backup has schema version {0}, this pct only knows \
+        versions up to {1}. Please update pct first.")]
+    SchemaTooNew(usize, usize),
+
+    #[error("\x1b[31mRusqlite Error:\n\x1b[0m{0}")]
+    Rusqlite(#[from] Error),
+
+    #[error("\x1b[31mRead/Write Error:\n\x1b[0m{0}")]
+    StdIOError(#[from] std::io::Error),
+
+    #[error(transparent)]
+    DatabaseError(#[from] DatabaseError),
+}
+
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("\x1b[31mInvalid Workspace Name:\n\x1b[0m{0}")]
+    InvalidName(String),
+
+    #[error("\x1b[31mWorkspace Not Found:\n\x1b[0m{0}")]
+    NotFound(String),
+
+    #[error("\x1b[31mWorkspace Exists:\n\x1b[0m{0}")]
+    AlreadyExists(String),
+
+    #[error("\x1b[31mRead/Write Error:\n\x1b[0m{0}")]
+    StdIOError(#[from] std::io::Error),
+
+    #[error(transparent)]
+    ConfigError(#[from] ConfigError),
+
+    #[error(transparent)]
+    DatabaseError(#[from] DatabaseError),
+}
+
+impl From<ureq::Error> for SyncError {
+    fn from(error: ureq::Error) -> Self {
+        SyncError::Http(Box::new(error))
+    }
+}
+
+// Points at the offending part of the input:
+//
+//   Invalid Filter: Unknown filter 'foo'
+//     state:started or foo:bar
+//                      ^^^^^^^
+#[derive(Debug)]
+pub struct ParseError {
+    /// "Filter" or "Command"
+    pub subject: &'static str,
+    pub message: String,
+    pub input: String,
+    /// Position and length of the offending part, counted in characters
+    pub position: usize,
+    pub length: usize,
+}
+
+impl ParseError {
+    pub fn new(message: &str, input: &str, position: usize, length: usize) -> Self {
+        ParseError {
+            subject: "Filter",
+            message: message.to_string(),
+            input: input.to_string(),
+            position,
+            length: length.max(1),
+        }
+    }
+
+    // An error in the command line around the filter, e.g. an unknown option.
+    pub fn command(message: &str, input: &str, position: usize, length: usize) -> Self {
+        ParseError {
+            subject: "Command",
+            ..ParseError::new(message, input, position, length)
+        }
+    }
+
+    // Show the error in the whole command line the filter was taken from.
+    pub fn within(self, input: &str, offset: usize) -> Self {
+        ParseError {
+            input: input.to_string(),
+            position: self.position + offset,
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "\x1b[31mInvalid {}: {}\x1b[0m", self.subject, self.message)?;
+        writeln!(f, "  {}", self.input)?;
+        write!(f, "  {}{}", " ".repeat(self.position), "^".repeat(self.length))
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,224 @@
+// exchange.rs
+
+use chrono::prelude::*;
+use clap::ValueEnum;
+use rusqlite::Connection;
+use std::collections::HashMap;
+use std::{fs, path::Path};
+
+use crate::crud::*;
+use crate::error::{CrudError, ExchangeError};
+use crate::filter::Filter;
+use crate::report::{self, Column};
+use crate::task::{start_of_day, Annotation, Task};
+use crate::{ics, taskwarrior, todotxt};
+
+// Supported file formats __________________________________________________________________________
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Format {
+    /// iCalendar (RFC 5545) VTODOs
+    Ics,
+    /// Taskwarrior JSON, as written by `task export`
+    Taskwarrior,
+    /// todo.txt, one task per line
+    TodoTxt,
+    /// Spreadsheet report with the chosen --columns (export only)
+    Csv,
+    /// GitHub task list grouped by project (export only)
+    Markdown,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Self, ExchangeError> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("ics") | Some("ical") => Ok(Format::Ics),
+            Some("json") => Ok(Format::Taskwarrior),
+            Some("txt") => Ok(Format::TodoTxt),
+            _ => Err(ExchangeError::UnknownFormat(format!(
+                "Can't detect the format of '{}', please specify it with --from.",
+                path.display()
+            ))),
+        }
+    }
+}
+
+// A task read from or written to a file, together with what is stored outside the tasks table.
+pub struct Record {
+    pub task: Task,
+    /// Project path like "Home.Garden", stored as a chain of parent project tasks
+    pub project: Option<String>,
+    pub annotations: Vec<Annotation>,
+}
+
+impl From<Task> for Record {
+    fn from(task: Task) -> Self {
+        Record {
+            task,
+            project: None,
+            annotations: Vec::new(),
+        }
+    }
+}
+
+// Public export & import interface ________________________________________________________________
+pub fn export_tasks(
+    conn: &Connection,
+    format: Format,
+    filter: &Filter,
+    columns: &[Column],
+) -> Result<String, ExchangeError> {
+    let all = get_tasks_or_empty(conn)?;
+    let tasks = filter.apply(&all, &all);
+    match format {
+        Format::Ics => Ok(ics::to_ics(&tasks).trim_end().to_string()),
+        Format::Taskwarrior => Ok(taskwarrior::to_json(&to_records(conn, &tasks, &all)?)),
+        Format::TodoTxt => Ok(todotxt::to_todo_txt(&to_records(conn, &tasks, &all)?)),
+        Format::Csv => Ok(report::to_csv(&tasks, &all, columns)),
+        Format::Markdown => Ok(report::to_markdown(&tasks, &all, columns)),
+    }
+}
+
+pub fn import_tasks(
+    conn: &Connection,
+    file: &Path,
+    format: Option<Format>,
+) -> Result<String, ExchangeError> {
+    let format = match format {
+        Some(format) => format,
+        None => Format::from_path(file)?,
+    };
+    let content = fs::read_to_string(file)?;
+    let mut records: Vec<Record> = match format {
+        Format::Ics => ics::from_ics(&content)?
+            .into_iter()
+            .map(Record::from)
+            .collect(),
+        Format::Taskwarrior => taskwarrior::from_json(&content)?,
+        Format::TodoTxt => todotxt::from_todo_txt(&content)?,
+        Format::Csv | Format::Markdown => {
+            return Err(ExchangeError::UnknownFormat(
+                "CSV and Markdown are reports, they can't be imported.".to_string(),
+            ))
+        }
+    };
+
+    // Either the whole file is imported or nothing at all:
+    let transaction = conn.unchecked_transaction().map_err(CrudError::from)?;
+    let (mut added, mut updated) = (0, 0);
+    for record in records.iter_mut() {
+        // Titles are unique, so a task from a file without uuids updates the one with its title.
+        // Other formats don't know projects, so a project stays one and keeps its children:
+        if get_task_by_uuid(&transaction, &record.task.uuid)?.is_none() {
+            if let Some(task) = get_task_by_title(&transaction, &record.task.title)? {
+                record.task.uuid = task.uuid;
+                record.task.project |= task.project;
+            }
+        }
+        if let (Some(project), true) = (&record.project, record.task.parent.is_empty()) {
+            record.task.parent = resolve_project(&transaction, project)?;
+        }
+        match upsert_task(&transaction, &record.task)? {
+            true => added += 1,
+            false => updated += 1,
+        }
+        for annotation in &record.annotations {
+            add_annotation(&transaction, &record.task.uuid, annotation)?;
+        }
+    }
+    transaction.commit().map_err(CrudError::from)?;
+    Ok(format!(
+        "Imported {} tasks from {}:\n  {} added, {} updated",
+        records.len(),
+        file.display(),
+        added,
+        updated
+    ))
+}
+
+// Helper functions --------------------------------------------------------------------------------
+
+// UTC date-time in the basic ISO 8601 format that iCalendar and Taskwarrior use.
+pub const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+pub fn parse_date_time(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT) {
+        return Some(date_time.and_utc());
+    }
+    // Floating times (without the trailing 'Z') are interpreted as local time:
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Local
+            .from_local_datetime(&date_time)
+            .single()
+            .map(|local| local.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .and_then(start_of_day)
+}
+
+// Project tasks are written as the project path of their children, not as tasks of their own.
+fn to_records(conn: &Connection, tasks: &[Task], all: &[Task]) -> Result<Vec<Record>, CrudError> {
+    let by_uuid: HashMap<&str, &Task> = all
+        .iter()
+        .map(|task| (task.uuid.as_str(), task))
+        .collect();
+    let mut records = Vec::new();
+    for task in tasks.iter().filter(|task| !task.project) {
+        records.push(Record {
+            task: task.clone(),
+            project: task.project_path(&by_uuid),
+            annotations: get_annotations(conn, &task.uuid)?,
+        });
+    }
+    Ok(records)
+}
+
+// Find or create the project tasks for a project path and return the uuid of the innermost one.
+pub fn resolve_project(conn: &Connection, path: &str) -> Result<String, CrudError> {
+    let mut parent = String::new();
+    for name in path.split('.').filter(|name| !name.is_empty()) {
+        let project = match get_task_by_title(conn, name)? {
+            Some(task) if task.project => task,
+            Some(mut task) => {
+                task.project = true;
+                upsert_task(conn, &task)?;
+                task
+            }
+            None => {
+                let mut task = Task::new(name.to_string(), 0);
+                task.project = true;
+                task.parent = parent;
+                upsert_task(conn, &task)?;
+                task
+            }
+        };
+        parent = project.uuid;
+    }
+    Ok(parent)
+}
+
+#[cfg(test)]
+#[test]
+fn test_import_keeps_projects() {
+    crate::config::use_test_config();
+    let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
+    crate::database::create_table(&conn).expect("Failed to create tables");
+    let garden = resolve_project(&conn, "Home.Garden").unwrap();
+    create_task(&conn, "Plant tomatoes".to_string(), &[]).unwrap();
+    conn.execute("UPDATE tasks SET parent = ? WHERE title = 'Plant tomatoes'", [&garden])
+        .unwrap();
+
+    // A Taskwarrior task with the project's title but its own uuid:
+    let file = std::env::temp_dir().join(format!("pocato-{}.json", uuid::Uuid::new_v4()));
+    fs::write(&file, r#"[{"uuid":"tw-1","description":"Garden","status":"pending"}]"#).unwrap();
+    import_tasks(&conn, &file, Some(Format::Taskwarrior)).unwrap();
+    let _ = fs::remove_file(&file);
+    let project = get_task_by_title(&conn, "Garden").unwrap().unwrap();
+    assert!(project.project && project.uuid == garden);
+    let task = get_task_by_title(&conn, "Plant tomatoes").unwrap().unwrap();
+    assert_eq!(task.parent, garden);
+}
@@ -0,0 +1,478 @@
+// filter.rs
+
+use chrono::prelude::*;
+use chrono::Duration;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+
+use crate::error::{CrudError, ParseError};
+use crate::search::search_uuids;
+use crate::task::{start_of_day, Task, TaskState};
+
+// Filter expressions ______________________________________________________________________________
+//
+//   state:started or (+work due.before:fri)      not project:Home      3-5      "quarterly report"
+//
+// Terms next to each other are combined with "and", "or" binds weaker than "and", "not" stronger.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    All,
+    Term(Term),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Term {
+    /// "3" or "3-5"
+    Id(i64, i64),
+    Uuid(String),
+    State(TaskState),
+    /// "+tag" or "-tag"
+    Tag(String, bool),
+    /// Matches the project and all of its sub projects
+    Project(String),
+    /// "priority:" matches tasks without a priority
+    Priority(Option<String>),
+    Date(
+        DateField,
+        Comparison,
+        Option<(DateTime<Utc>, DateTime<Utc>)>,
+    ),
+    /// Bare words and "title:..." match case insensitive parts of the title
+    Title(String),
+    /// "text:~..." is a full-text search in titles, notes and annotations. The matching uuids
+    /// are looked up by `Filter::search` before the filter is applied.
+    Text(String, HashSet<String>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum DateField {
+    Due,
+    Created,
+    Ended,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Comparison {
+    On,
+    Before,
+    After,
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Filter, ParseError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            input,
+            tokens: &tokens,
+            position: 0,
+        };
+        if tokens.is_empty() {
+            return Ok(Filter::All);
+        }
+        let filter = parser.or()?;
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(token.error(input, "Unexpected token")),
+        }
+    }
+
+    pub fn matches(&self, task: &Task, by_uuid: &HashMap<&str, &Task>) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Term(term) => term.matches(task, by_uuid),
+            Filter::Not(filter) => !filter.matches(task, by_uuid),
+            Filter::And(left, right) => left.matches(task, by_uuid) && right.matches(task, by_uuid),
+            Filter::Or(left, right) => left.matches(task, by_uuid) || right.matches(task, by_uuid),
+        }
+    }
+
+    // Run the full-text searches of all "text:~..." terms.
+    pub fn search(mut self, conn: &Connection) -> Result<Filter, CrudError> {
+        let mut filters = vec![&mut self];
+        while let Some(filter) = filters.pop() {
+            match filter {
+                Filter::Term(Term::Text(text, uuids)) => *uuids = search_uuids(conn, text)?,
+                Filter::Not(filter) => filters.push(filter),
+                Filter::And(left, right) | Filter::Or(left, right) => {
+                    filters.push(left);
+                    filters.push(right);
+                }
+                Filter::All | Filter::Term(_) => (),
+            }
+        }
+        Ok(self)
+    }
+
+    // Keep the tasks that match, their projects are looked up among all tasks.
+    pub fn apply(&self, tasks: &[Task], all: &[Task]) -> Vec<Task> {
+        let by_uuid: HashMap<&str, &Task> = all
+            .iter()
+            .map(|task| (task.uuid.as_str(), task))
+            .collect();
+        tasks
+            .iter()
+            .filter(|task| self.matches(task, &by_uuid))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Term {
+    fn matches(&self, task: &Task, by_uuid: &HashMap<&str, &Task>) -> bool {
+        match self {
+            Term::Id(from, to) => (*from..=*to).contains(&task.id),
+            Term::Uuid(uuid) => task.uuid.starts_with(uuid.as_str()),
+            Term::State(state) => task.state.to_string() == state.to_string(),
+            Term::Tag(tag, present) => task.tags.contains(tag) == *present,
+            Term::Project(name) => task.project_path(by_uuid).is_some_and(|path| {
+                let (path, name) = (path.to_lowercase(), name.to_lowercase());
+                path == name || path.starts_with(&format!("{}.", name))
+            }),
+            Term::Priority(priority) => task.priority == *priority,
+            Term::Date(field, comparison, range) => {
+                let date = match field {
+                    DateField::Due => task.due_utc(),
+                    DateField::Created => task.created_utc(),
+                    DateField::Ended => task.ended_utc(),
+                };
+                match (date, range, comparison) {
+                    (date, None, _) => date.is_none(),
+                    (None, Some(_), _) => false,
+                    (Some(date), Some((start, end)), Comparison::On) => {
+                        *start <= date && date < *end
+                    }
+                    (Some(date), Some((start, _)), Comparison::Before) => date < *start,
+                    (Some(date), Some((_, end)), Comparison::After) => date >= *end,
+                }
+            }
+            Term::Title(text) => task.title.to_lowercase().contains(&text.to_lowercase()),
+            Term::Text(_, uuids) => uuids.contains(&task.uuid),
+        }
+    }
+}
+
+// Dates in filters and modifications: "today", "tomorrow", "fri", "2026-10-01", ...
+// Returns the time span [start, end) the value stands for, e.g. the whole day for a date.
+pub fn parse_date(value: &str) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let today = Local::now().date_naive();
+    let day = |date: NaiveDate| Some((start_of_day(date)?, start_of_day(date.succ_opt()?)?));
+    let value = value.to_lowercase();
+    match value.as_str() {
+        "now" => Some((Utc::now(), Utc::now())),
+        "today" => day(today),
+        "tomorrow" => day(today + Duration::days(1)),
+        "yesterday" => day(today - Duration::days(1)),
+        _ => {
+            if let Ok(weekday) = value.parse::<Weekday>() {
+                // The next such day, today included:
+                let offset = weekday.num_days_from_monday() as i64
+                    - today.weekday().num_days_from_monday() as i64;
+                return day(today + Duration::days(offset.rem_euclid(7)));
+            }
+            if let Ok(date) = NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
+                return day(date);
+            }
+            let date_time = DateTime::parse_from_rfc3339(&value)
+                .ok()?
+                .with_timezone(&Utc);
+            Some((date_time, date_time))
+        }
+    }
+}
+
+// Durations like "30d", "12h" or "2w" (minutes, hours, days and weeks), at least one unit long.
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let unit = value.chars().last()?;
+    let count: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    if count < 1 {
+        return None;
+    }
+    match unit {
+        'm' => Some(Duration::minutes(count)),
+        'h' => Some(Duration::hours(count)),
+        'd' => Some(Duration::days(count)),
+        'w' => Some(Duration::weeks(count)),
+        _ => None,
+    }
+}
+
+// Tokenizer _______________________________________________________________________________________
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    /// Position and length in characters, for error messages
+    start: usize,
+    length: usize,
+}
+
+impl Token {
+    fn error(&self, input: &str, message: &str) -> ParseError {
+        ParseError::new(message, input, self.start, self.length)
+    }
+}
+
+// Split on whitespace and parentheses, double quotes group words: title:"quarterly report"
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut word_start = 0;
+    let mut quote_start = None;
+
+    let chars: Vec<char> = input.chars().collect();
+    for (index, char) in chars.iter().copied().enumerate() {
+        match (char, quote_start) {
+            ('"', None) => quote_start = Some(index),
+            ('"', Some(_)) => quote_start = None,
+            (_, Some(_)) => word.push(char),
+            (char, None) if char.is_whitespace() || char == '(' || char == ')' => {
+                if !word.is_empty() || index > word_start {
+                    tokens.push(Token {
+                        kind: TokenKind::Word(std::mem::take(&mut word)),
+                        start: word_start,
+                        length: index - word_start,
+                    });
+                }
+                match char {
+                    '(' => tokens.push(Token {
+                        kind: TokenKind::Open,
+                        start: index,
+                        length: 1,
+                    }),
+                    ')' => tokens.push(Token {
+                        kind: TokenKind::Close,
+                        start: index,
+                        length: 1,
+                    }),
+                    _ => (),
+                }
+                word_start = index + 1;
+            }
+            _ => word.push(char),
+        }
+    }
+    if let Some(start) = quote_start {
+        return Err(ParseError::new("Unterminated quote", input, start, 1));
+    }
+    if !word.is_empty() || chars.len() > word_start {
+        tokens.push(Token {
+            kind: TokenKind::Word(word),
+            start: word_start,
+            length: chars.len() - word_start,
+        });
+    }
+    Ok(tokens)
+}
+
+// Recursive descent parser ________________________________________________________________________
+struct Parser<'a> {
+    input: &'a str,
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token { kind: TokenKind::Word(word), .. })
+            if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    // or := and ("or" and)*
+    fn or(&mut self) -> Result<Filter, ParseError> {
+        let mut filter = self.and()?;
+        while self.peek_keyword("or") {
+            self.next();
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    // and := not ("and"? not)*
+    fn and(&mut self) -> Result<Filter, ParseError> {
+        let mut filter = self.not()?;
+        loop {
+            if self.peek_keyword("and") {
+                self.next();
+            } else if self.peek_keyword("or") || self.peek().is_none() {
+                return Ok(filter);
+            } else if let Some(Token {
+                kind: TokenKind::Close,
+                ..
+            }) = self.peek()
+            {
+                return Ok(filter);
+            }
+            filter = Filter::And(Box::new(filter), Box::new(self.not()?));
+        }
+    }
+
+    // not := "not" not | "(" or ")" | term
+    fn not(&mut self) -> Result<Filter, ParseError> {
+        let end_of_input = || {
+            ParseError::new(
+                "Filter ends too early",
+                self.input,
+                self.input.chars().count(),
+                1,
+            )
+        };
+        let token = self.next().ok_or_else(end_of_input)?;
+        match &token.kind {
+            TokenKind::Word(word) if word.eq_ignore_ascii_case("not") => {
+                Ok(Filter::Not(Box::new(self.not()?)))
+            }
+            TokenKind::Open => {
+                let filter = self.or()?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::Close,
+                        ..
+                    }) => Ok(filter),
+                    _ => Err(token.error(self.input, "Parenthesis is never closed")),
+                }
+            }
+            TokenKind::Close => Err(token.error(self.input, "Unmatched closing parenthesis")),
+            TokenKind::Word(word) => match parse_term(word) {
+                Ok(term) => Ok(Filter::Term(term)),
+                Err(message) => Err(token.error(self.input, &message)),
+            },
+        }
+    }
+}
+
+pub fn parse_term(word: &str) -> Result<Term, String> {
+    if let Some(tag) = word.strip_prefix('+').filter(|tag| !tag.is_empty()) {
+        return Ok(Term::Tag(tag.to_string(), true));
+    }
+    if let Some(tag) = word.strip_prefix('-').filter(|tag| !tag.is_empty()) {
+        return Ok(Term::Tag(tag.to_string(), false));
+    }
+    if let Some(range) = parse_id_range(word) {
+        return Ok(range);
+    }
+    let Some((key, value)) = word.split_once(':') else {
+        return Ok(Term::Title(word.to_string()));
+    };
+
+    let (field, comparison) = match key.split_once('.') {
+        Some((field, "before")) => (field, Comparison::Before),
+        Some((field, "after")) => (field, Comparison::After),
+        Some((_, modifier)) => return Err(format!("Unknown modifier '{}'", modifier)),
+        None => (key, Comparison::On),
+    };
+    let date_field = match field {
+        "due" => Some(DateField::Due),
+        "created" | "entry" => Some(DateField::Created),
+        "ended" | "end" => Some(DateField::Ended),
+        _ => None,
+    };
+    if let Some(date_field) = date_field {
+        return match (value, parse_date(value)) {
+            ("" | "none", _) => Ok(Term::Date(date_field, comparison, None)),
+            (_, Some(range)) => Ok(Term::Date(date_field, comparison, Some(range))),
+            (_, None) => Err(format!("Invalid date '{}'", value)),
+        };
+    }
+    if let Comparison::Before | Comparison::After = comparison {
+        return Err(format!("'{}' can't be compared with before/after", field));
+    }
+
+    match key {
+        "state" | "status" => match TaskState::from_name(value) {
+            Some(state) => Ok(Term::State(state)),
+            None => Err(format!("Unknown state '{}'", value)),
+        },
+        "project" | "pro" => Ok(Term::Project(value.to_string())),
+        "tag" | "tags" => Ok(Term::Tag(value.to_string(), true)),
+        "priority" | "pri" => match value.to_uppercase().as_str() {
+            "" => Ok(Term::Priority(None)),
+            priority @ ("H" | "M" | "L") => Ok(Term::Priority(Some(priority.to_string()))),
+            _ => Err(format!("Unknown priority '{}', use H, M or L", value)),
+        },
+        "uuid" => Ok(Term::Uuid(value.to_string())),
+        "title" | "description" => Ok(Term::Title(value.to_string())),
+        "text" => match value.trim_start_matches('~').trim() {
+            "" => Err("Please enter something to search for".to_string()),
+            text => Ok(Term::Text(text.to_string(), HashSet::new())),
+        },
+        _ => Err(format!("Unknown filter '{}'", key)),
+    }
+}
+
+fn parse_id_range(word: &str) -> Option<Term> {
+    let (from, to) = word.split_once('-').unwrap_or((word, word));
+    match (from.parse::<i64>(), to.parse::<i64>()) {
+        (Ok(from), Ok(to)) if from <= to => Some(Term::Id(from, to)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_filter() {
+    let mut home = Task::new("Home".to_string(), 1);
+    home.project = true;
+    let mut paint = Task::new("Paint the fence".to_string(), 2);
+    paint.parent = home.uuid.clone();
+    paint.tags = vec!["outside".to_string()];
+    paint.state = TaskState::Started;
+    paint.due = start_of_day(Local::now().date_naive()).map(|today| today.to_string());
+    let mut report = Task::new("Quarterly report".to_string(), 3);
+    report.priority = Some("H".to_string());
+    let tasks = vec![home, paint, report];
+
+    let titles = |filter: &str| -> Vec<String> {
+        let filter = Filter::parse(filter).expect("Failed to parse filter");
+        filter
+            .apply(&tasks, &tasks)
+            .into_iter()
+            .map(|task| task.title)
+            .collect()
+    };
+    assert_eq!(
+        titles(""),
+        vec!["Home", "Paint the fence", "Quarterly report"]
+    );
+    assert_eq!(titles("project:home"), vec!["Paint the fence"]);
+    assert_eq!(titles("+outside due:today"), vec!["Paint the fence"]);
+    assert_eq!(
+        titles("state:started or priority:h"),
+        vec!["Paint the fence", "Quarterly report"]
+    );
+    assert_eq!(titles("not (2-3)"), vec!["Home"]);
+    assert_eq!(titles("due: -outside \"REPORT\""), vec!["Quarterly report"]);
+    assert_eq!(
+        titles("due.before:tomorrow and due.after:yesterday"),
+        vec!["Paint the fence"]
+    );
+
+    let error = Filter::parse("state:started or foo:bar").unwrap_err();
+    assert_eq!(
+        error.to_string().lines().last(),
+        Some("                   ^^^^^^^")
+    );
+    assert!(Filter::parse("(+work").is_err());
+    assert!(Filter::parse("+work)").is_err());
+
+    assert_eq!(parse_duration("2w"), Some(Duration::days(14)));
+    assert!(parse_duration("0d").is_none() && parse_duration("-3d").is_none());
+}
@@ -0,0 +1,101 @@
+// font.rs
+
+use dialoguer::Confirm;
+use std::env;
+use std::process::Command;
+
+use crate::prompt;
+
+// Nerd Font detection _____________________________________________________________________________
+//
+// Nerd Font icons only render if the terminal has such a font, otherwise they show up as boxes.
+// Detection goes from cheap to interactive: terminals that bundle the icons, installed fonts
+// according to fontconfig and, only if that didn't settle it and a person is there to answer,
+// a test glyph.
+pub struct FontDetection {
+    pub nerd_font: bool,
+    pub reason: String,
+}
+
+const TEST_GLYPH: &str = "\u{f046}";
+
+pub fn detect_nerd_font(ask: bool) -> FontDetection {
+    if let Some(detection) = terminal_hint() {
+        return detection;
+    }
+    if let Some(detection) = fontconfig() {
+        return detection;
+    }
+    if ask && prompt::can_prompt() {
+        let prompt = format!("Do you see a checked box here: {} ?", TEST_GLYPH);
+        if let Ok(nerd_font) = Confirm::new().with_prompt(prompt).interact() {
+            return FontDetection {
+                nerd_font,
+                reason: "you answered so".to_string(),
+            };
+        }
+    }
+    FontDetection {
+        nerd_font: false,
+        reason: "no Nerd Font was found".to_string(),
+    }
+}
+
+// Helper functions --------------------------------------------------------------------------------
+
+// WezTerm, kitty and Ghostty ship the Nerd Font symbols, the Linux console can't show them at all.
+fn terminal_hint() -> Option<FontDetection> {
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+    let term = env::var("TERM").unwrap_or_default();
+    let nerd_font = match (term_program.as_str(), term.as_str()) {
+        ("WezTerm" | "ghostty", _) | (_, "xterm-kitty" | "xterm-ghostty") => true,
+        (_, "linux") => false,
+        _ => return None,
+    };
+    let terminal = match term_program.is_empty() {
+        true => term,
+        false => term_program,
+    };
+    Some(FontDetection {
+        nerd_font,
+        reason: format!("the terminal is {}", terminal),
+    })
+}
+
+// Installed Nerd Fonts are named e.g. "JetBrainsMono Nerd Font" or "Hack NF". Without fc-list
+// (Windows, MacOS without fontconfig) there is nothing to tell.
+fn fontconfig() -> Option<FontDetection> {
+    let output = Command::new("fc-list").args([":", "family"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let families = String::from_utf8_lossy(&output.stdout).to_string();
+    let nerd_font = families.lines().find(|family| is_nerd_font(family));
+    Some(match nerd_font {
+        Some(family) => FontDetection {
+            nerd_font: true,
+            reason: format!("'{}' is installed", family.split(',').next().unwrap_or(family)),
+        },
+        None => FontDetection {
+            nerd_font: false,
+            reason: "fontconfig lists no Nerd Font".to_string(),
+        },
+    })
+}
+
+fn is_nerd_font(family: &str) -> bool {
+    family.split(',').any(|name| {
+        let name = name.trim();
+        name.contains("Nerd Font") || name.ends_with(" NF") || name.contains(" NF ")
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn test_is_nerd_font() {
+    assert!(is_nerd_font("JetBrainsMono Nerd Font,JetBrainsMono Nerd Font Mono"));
+    assert!(is_nerd_font("Hack NF"));
+    assert!(is_nerd_font("DejaVu Sans,Symbols Nerd Font"));
+    assert!(!is_nerd_font("DejaVu Sans Mono"));
+    assert!(!is_nerd_font("NFL Sans"));
+}
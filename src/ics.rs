@@ -0,0 +1,386 @@
+// ics.rs
+
+use chrono::prelude::*;
+
+use crate::error::ExchangeError;
+use crate::exchange::{parse_date_time, DATE_TIME_FORMAT};
+use crate::task::{Task, TaskState};
+
+// iCalendar (RFC 5545) VTODO serialization ________________________________________________________
+const PRODID: &str = concat!("-//CodedCraft//Pocato ", env!("CARGO_PKG_VERSION"), "//EN");
+
+pub fn to_ics(tasks: &[Task]) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+    ];
+    for task in tasks {
+        lines.extend(to_vtodo(task));
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+pub fn to_vtodo(task: &Task) -> Vec<String> {
+    let (status, percent) = state_to_status(&task.state);
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", task.uuid),
+        format!("DTSTAMP:{}", Utc::now().format(DATE_TIME_FORMAT)),
+    ];
+    if let Some(created) = task.created_utc() {
+        lines.push(format!("CREATED:{}", created.format(DATE_TIME_FORMAT)));
+    }
+    lines.push(format!("SUMMARY:{}", escape_text(&task.title)));
+    lines.push(format!("STATUS:{}", status));
+    lines.push(format!("PERCENT-COMPLETE:{}", percent));
+    if let (TaskState::Finished, Some(ended)) = (&task.state, task.ended_utc()) {
+        lines.push(format!("COMPLETED:{}", ended.format(DATE_TIME_FORMAT)));
+    }
+    if let Some(due) = task.due_utc() {
+        lines.push(format!("DUE:{}", due.format(DATE_TIME_FORMAT)));
+    }
+    if let Some(priority) = task.priority.as_deref().and_then(priority_to_ics) {
+        lines.push(format!("PRIORITY:{}", priority));
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|tag| escape_text(tag)).collect();
+        lines.push(format!("CATEGORIES:{}", tags.join(",")));
+    }
+    // Pocato knows more states than iCalendar, keep the exact one for round-trips:
+    lines.push(format!("X-POCATO-STATE:{}", task.state.to_string()));
+    if task.project {
+        lines.push("X-POCATO-PROJECT:TRUE".to_string());
+    }
+    if !task.parent.is_empty() {
+        lines.push(format!("RELATED-TO;RELTYPE=PARENT:{}", task.parent));
+    }
+    for dependency in &task.depends {
+        lines.push(format!("RELATED-TO;RELTYPE=DEPENDS-ON:{}", dependency));
+    }
+    lines.push("END:VTODO".to_string());
+    lines
+}
+
+pub fn from_ics(ics: &str) -> Result<Vec<Task>, ExchangeError> {
+    let mut tasks = Vec::new();
+    let mut current: Option<VTodo> = None;
+    // Nested components (e.g. VALARM) inside a VTODO are skipped:
+    let mut nested = 0;
+
+    for (number, line) in unfold_lines(ics) {
+        let property = Property::parse(&line).ok_or_else(|| {
+            ExchangeError::InvalidFormat("iCalendar", format!("Line {}: '{}'", number, line))
+        })?;
+
+        match (property.name.as_str(), property.value.as_str()) {
+            ("BEGIN", "VTODO") => current = Some(VTodo::default()),
+            ("END", "VTODO") => match current.take() {
+                Some(vtodo) => tasks.push(vtodo.into_task(number)?),
+                None => {
+                    return Err(ExchangeError::InvalidFormat(
+                        "iCalendar",
+                        format!("Line {}: END:VTODO without BEGIN:VTODO", number),
+                    ))
+                }
+            },
+            ("BEGIN", _) if current.is_some() => nested += 1,
+            ("END", _) if current.is_some() && nested > 0 => nested -= 1,
+            _ => {
+                if let (Some(vtodo), 0) = (current.as_mut(), nested) {
+                    vtodo.set(property);
+                }
+            }
+        }
+    }
+
+    Ok(tasks)
+}
+
+// Helper functions --------------------------------------------------------------------------------
+#[derive(Default)]
+struct VTodo {
+    uid: Option<String>,
+    summary: Option<String>,
+    created: Option<String>,
+    completed: Option<String>,
+    due: Option<String>,
+    priority: Option<u8>,
+    categories: Vec<String>,
+    depends: Vec<String>,
+    status: Option<String>,
+    percent: Option<u8>,
+    pocato_state: Option<String>,
+    project: bool,
+    parent: String,
+}
+
+impl VTodo {
+    fn set(&mut self, property: Property) {
+        match property.name.as_str() {
+            "UID" => self.uid = Some(property.value),
+            "SUMMARY" => self.summary = Some(unescape_text(&property.value)),
+            "CREATED" => self.created = Some(property.value),
+            "COMPLETED" => self.completed = Some(property.value),
+            "DUE" => self.due = Some(property.value),
+            "PRIORITY" => self.priority = property.value.trim().parse().ok(),
+            "CATEGORIES" => self.categories.extend(
+                split_list(&property.value)
+                    .iter()
+                    .map(|category| unescape_text(category).replace(' ', "-"))
+                    .filter(|category| !category.is_empty()),
+            ),
+            "STATUS" => self.status = Some(property.value.to_uppercase()),
+            "PERCENT-COMPLETE" => self.percent = property.value.trim().parse().ok(),
+            "X-POCATO-STATE" => self.pocato_state = Some(property.value),
+            "X-POCATO-PROJECT" => self.project = property.value.eq_ignore_ascii_case("TRUE"),
+            "RELATED-TO" => {
+                // RELTYPE defaults to PARENT when missing (RFC 5545, 3.2.15):
+                let reltype = property.param("RELTYPE").unwrap_or("PARENT");
+                if reltype.eq_ignore_ascii_case("PARENT") {
+                    self.parent = property.value;
+                } else if reltype.eq_ignore_ascii_case("DEPENDS-ON") {
+                    self.depends.push(property.value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn into_task(self, line: usize) -> Result<Task, ExchangeError> {
+        let (uuid, title) = match (self.uid, self.summary) {
+            (Some(uuid), Some(title)) if !title.trim().is_empty() => (uuid, title),
+            _ => {
+                return Err(ExchangeError::InvalidFormat(
+                    "iCalendar",
+                    format!("VTODO ending on line {} needs a UID and a SUMMARY", line),
+                ))
+            }
+        };
+
+        let state = match self.pocato_state.as_deref().and_then(TaskState::from_name) {
+            Some(state) => state,
+            None => status_to_state(self.status.as_deref(), self.percent),
+        };
+
+        let mut task = Task::new(title, 0);
+        task.uuid = uuid;
+        task.state = state;
+        task.project = self.project;
+        task.parent = self.parent;
+        task.tags = self.categories;
+        task.depends = self.depends;
+        task.priority = self.priority.and_then(priority_from_ics);
+        if let Some(created) = self.created.as_deref().and_then(parse_date_time) {
+            task.created = created.to_string();
+        }
+        task.due = self
+            .due
+            .as_deref()
+            .and_then(parse_date_time)
+            .map(|due| due.to_string());
+        if task.state.is_closed() {
+            let ended = self.completed.as_deref().and_then(parse_date_time);
+            task.ended = Some(ended.unwrap_or_else(Utc::now).to_string());
+        }
+        Ok(task)
+    }
+}
+
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    // Split "NAME;PARAM=VALUE:value" while ignoring colons inside quoted parameter values.
+    fn parse(line: &str) -> Option<Self> {
+        let mut in_quotes = false;
+        let colon = line.char_indices().find_map(|(index, char)| match char {
+            '"' => {
+                in_quotes = !in_quotes;
+                None
+            }
+            ':' if !in_quotes => Some(index),
+            _ => None,
+        })?;
+
+        let mut head = line[..colon].split(';');
+        let name = head.next()?.trim().to_uppercase();
+        if name.is_empty() {
+            return None;
+        }
+        let params = head
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| (key.to_uppercase(), value.trim_matches('"').to_string()))
+            .collect();
+
+        Some(Property {
+            name,
+            params,
+            value: line[colon + 1..].to_string(),
+        })
+    }
+
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn state_to_status(state: &TaskState) -> (&'static str, u8) {
+    match state {
+        TaskState::Pending | TaskState::Blocked | TaskState::Someday => ("NEEDS-ACTION", 0),
+        TaskState::Started | TaskState::Paused => ("IN-PROCESS", 50),
+        TaskState::Finished => ("COMPLETED", 100),
+        TaskState::Cancelled => ("CANCELLED", 0),
+    }
+}
+
+fn status_to_state(status: Option<&str>, percent: Option<u8>) -> TaskState {
+    match (status, percent.unwrap_or(0)) {
+        (Some("COMPLETED"), _) => TaskState::Finished,
+        (Some("CANCELLED"), _) => TaskState::Cancelled,
+        (Some("IN-PROCESS"), _) => TaskState::Started,
+        (None, 100) => TaskState::Finished,
+        (None, 1..=99) => TaskState::Started,
+        _ => TaskState::Pending,
+    }
+}
+
+// iCalendar priorities go from 1 (highest) to 9 (lowest), 0 means undefined:
+fn priority_to_ics(priority: &str) -> Option<u8> {
+    match priority {
+        "H" => Some(1),
+        "M" => Some(5),
+        "L" => Some(9),
+        _ => None,
+    }
+}
+
+fn priority_from_ics(priority: u8) -> Option<String> {
+    match priority {
+        1..=4 => Some("H".to_string()),
+        5 => Some("M".to_string()),
+        6..=9 => Some("L".to_string()),
+        _ => None,
+    }
+}
+
+// Split a list value on commas that are not escaped:
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut escaped = false;
+    for char in value.chars() {
+        match (char, escaped) {
+            (',', false) => items.push(String::new()),
+            _ => items.last_mut().unwrap().push(char),
+        }
+        escaped = char == '\\' && !escaped;
+    }
+    items
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(char) = chars.next() {
+        match (char, chars.clone().next()) {
+            ('\\', Some('n' | 'N')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some(escaped @ ('\\' | ';' | ','))) => {
+                unescaped.push(escaped);
+                chars.next();
+            }
+            _ => unescaped.push(char),
+        }
+    }
+    unescaped
+}
+
+// Content lines are limited to 75 octets, longer lines continue on the next line after a space:
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+    for char in line.chars() {
+        if octets + char.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(char);
+        octets += char.len_utf8();
+    }
+    folded
+}
+
+// Join folded lines again and keep the line number of each logical line for error messages:
+fn unfold_lines(ics: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in ics.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, previous))) => previous.push_str(continuation),
+            _ if line.trim().is_empty() => (),
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+#[test]
+fn test_ics_round_trip() {
+    let mut finished = Task::new(
+        "Write the quarterly report; then, send it\nto Bob".to_string(),
+        1,
+    );
+    finished.state = TaskState::Finished;
+    let mut blocked = Task::new("Ünïcödé ".repeat(20).trim().to_string(), 2);
+    blocked.state = TaskState::Blocked;
+    blocked.parent = finished.uuid.clone();
+
+    let ics = to_ics(&[finished.clone(), blocked.clone()]);
+    assert!(ics
+        .lines()
+        .all(|line| line.trim_end_matches('\r').len() <= 75));
+    assert!(ics.contains("STATUS:COMPLETED\r\nPERCENT-COMPLETE:100\r\n"));
+
+    let tasks = from_ics(&ics).expect("Failed to parse exported iCalendar");
+    assert_eq!(tasks.len(), 2);
+    for (imported, original) in tasks.iter().zip([finished, blocked]) {
+        assert_eq!(imported.uuid, original.uuid);
+        assert_eq!(imported.title, original.title);
+        assert_eq!(imported.state.to_string(), original.state.to_string());
+        assert_eq!(imported.parent, original.parent);
+        assert_eq!(
+            imported.created_utc().map(|created| created.timestamp()),
+            original.created_utc().map(|created| created.timestamp())
+        );
+    }
+
+    // VTODOs from other clients without pocato's extension properties:
+    let foreign = "BEGIN:VCALENDAR\nBEGIN:VTODO\nUID:abc\nSUMMARY:Call Alice\nSTATUS:IN-PROCESS\n\
+        BEGIN:VALARM\nSUMMARY:Reminder\nEND:VALARM\nEND:VTODO\nEND:VCALENDAR\n";
+    let tasks = from_ics(foreign).expect("Failed to parse foreign iCalendar");
+    assert_eq!(tasks[0].title, "Call Alice");
+    assert_eq!(tasks[0].state.to_string(), "Started");
+}
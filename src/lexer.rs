@@ -1,57 +1,206 @@
 // lexer.rs
 
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Utc};
+use clap::{CommandFactory, FromArgMatches, Parser};
 use rusqlite::Connection;
 
+use crate::backup::{backup_database, restore_database};
+use crate::caldav::sync_caldav;
+use crate::color::{self, ColorChoice};
+use crate::alias::expand_aliases;
+use crate::completion::{self, complete, completion_script};
+use crate::config::*;
 use crate::crud::*;
-use crate::error::CliError;
-use crate::task::TaskState;
+use crate::database::before_command;
+use crate::error::{CliError, CrudError, SyncError};
+use crate::exchange::{export_tasks, import_tasks};
+use crate::filter::{parse_duration, Filter};
+use crate::parser::*;
+use crate::report::TABLE_COLUMNS;
+use crate::picker::pick_tasks;
+use crate::prompt::{self, InputMode};
+use crate::search::read_search;
+use crate::shell::run_shell;
+use crate::workspace::*;
 
 // Clap Setup ______________________________________________________________________________________
+//
+// Clap only takes the global options, the rest of the command line is tokenized below and parsed
+// by parser.rs.
 #[derive(Debug, Parser)]
-#[command(name = "cargo")]
+#[command(name = "pct", override_usage = "pct [OPTIONS] [FILTER] <COMMAND> [ARGUMENTS]")]
 struct Cli {
-    #[command(subcommand)]
-    command: Commands,
+    /// Use this workspace instead of the active one
+    #[arg(long)]
+    workspace: Option<String>,
+    /// Colored output: auto (if $NO_COLOR isn't set and output is a terminal), always or never
+    #[arg(long, value_enum, default_value = "auto")]
+    color: ColorChoice,
+    /// Override a setting for this run, e.g. `--config theme=light` (repeatable)
+    #[arg(long = "config", id = "config_overrides", value_name = "KEY=VALUE")]
+    config_overrides: Vec<String>,
+    /// Answer yes to confirmations, e.g. before deleting a task
+    #[arg(long)]
+    yes: bool,
+    /// Never ask anything, fail with exit code 4 where a question would be needed
+    #[arg(long)]
+    no_input: bool,
+    /// Filter, command and its arguments, e.g. `+work due.before:fri list`
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "ARGUMENTS")]
+    args: Vec<String>,
 }
 
-#[derive(Debug, Subcommand)]
-enum Commands {
-    Add { create_args: Vec<String> },
-    Show { task_id: Option<String> },
-    Start { task_id: String },
-    Block { task_id: String },
-    Someday { task_id: String },
-    Cancel { task_id: String },
-    Pause { task_id: String },
-    Finish { task_id: String },
-    Delete { task_id: String },
+// Tokenizer _______________________________________________________________________________________
+//
+// The shell has already split the command line into arguments, every argument is a token. Tokens
+// remember where they are in the command line, so errors can point at them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// "--name" or "--name=value"
+    Flag(String, Option<String>),
+    /// "+tag" or "-tag"
+    Tag(String, bool),
+    /// "key:value" like "priority:H" or "due.before:fri", the key in lowercase
+    Attribute(String, String),
+    Word,
 }
 
-// Public Lexer interface __________________________________________________________________________
-pub fn lexer_handler(conn: &Connection) {
-    let args = Cli::parse();
-    match parse_cli(conn, args) {
-        Ok(success) => println!("{}", success),
-        Err(err) => eprintln!("{}", err),
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// The argument as it was entered
+    pub text: String,
+    /// Position and length in the command line, counted in characters
+    pub start: usize,
+    pub length: usize,
+}
+
+// The command line as shown in error messages ("pct +work list") and its tokens. After "--" every
+// argument is a word, e.g. for `pct add -- --verbose flag is broken`.
+pub fn tokenize(args: &[String]) -> (String, Vec<Token>) {
+    let mut line = "pct".to_string();
+    let mut tokens = Vec::new();
+    let mut words_only = false;
+    for arg in args {
+        // Empty arguments are shown as "", so errors can point at them:
+        let shown = if arg.is_empty() { "\"\"" } else { arg.as_str() };
+        line.push(' ');
+        let start = line.chars().count();
+        line.push_str(shown);
+        let kind = match (words_only, arg.as_str()) {
+            (false, "--") => {
+                words_only = true;
+                continue;
+            }
+            (false, _) => token_kind(arg),
+            (true, _) => TokenKind::Word,
+        };
+        tokens.push(Token {
+            kind,
+            text: arg.clone(),
+            start,
+            length: shown.chars().count(),
+        });
+    }
+    (line, tokens)
+}
+
+fn token_kind(arg: &str) -> TokenKind {
+    let is_name = |name: &str| !name.is_empty() && !name.contains(char::is_whitespace);
+    if let Some(flag) = arg.strip_prefix("--").filter(|flag| is_name(flag)) {
+        return match flag.split_once('=') {
+            Some((name, value)) => TokenKind::Flag(name.to_string(), Some(value.to_string())),
+            None => TokenKind::Flag(flag.to_string(), None),
+        };
+    }
+    if let Some(tag) = arg.strip_prefix('+').filter(|tag| is_name(tag)) {
+        return TokenKind::Tag(tag.to_string(), true);
+    }
+    if let Some(tag) = arg.strip_prefix('-').filter(|tag| is_name(tag)) {
+        return TokenKind::Tag(tag.to_string(), false);
+    }
+    let is_key = |key: &str| {
+        !key.is_empty() && key.chars().all(|char| char.is_ascii_alphabetic() || char == '.')
     };
+    match arg.split_once(':') {
+        Some((key, value)) if is_key(key) => {
+            TokenKind::Attribute(key.to_lowercase(), value.to_string())
+        }
+        _ => TokenKind::Word,
+    }
+}
+
+// Public Lexer interface __________________________________________________________________________
+pub fn lexer_handler() {
+    let matches = cli_command().get_matches();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    color::init(args.color);
+    prompt::init(InputMode {
+        yes: args.yes,
+        no_input: args.no_input,
+    });
+    set_cli_overrides(args.config_overrides.clone());
+    // Aliases can't hide commands, so the words are enough to tell:
+    let words: Vec<&str> = args.args.iter().map(String::as_str).collect();
+    set_read_only(matches!(words[..], ["config", "validate" | "list" | "get", ..]));
+    // An alias is one command line, a macro several, which stop at the first error:
+    let result = expand_aliases(&args.args).and_then(|command_lines| {
+        for command_line in command_lines {
+            let success = run_line(&command_line, args.workspace.as_deref())?;
+            println!("{}", color::stdout(success));
+        }
+        Ok(())
+    });
+    if let Err(err) = result {
+        eprintln!("{}", color::stderr(&err));
+        std::process::exit(err.exit_code());
+    }
 }
 
 // Private parser functions ________________________________________________________________________
-fn parse_cli(conn: &Connection, args: Cli) -> Result<String, CliError> {
-    match args.command {
-        Commands::Add { create_args } => {
-            let title = create_args.join(" ");
+fn run_line(args: &[String], workspace_flag: Option<&str>) -> Result<String, CliError> {
+    let command_line = parse(args)?;
+    let workspace = match command_line.command {
+        // Workspace and config commands still work when the active workspace is gone:
+        Command::Workspace { .. }
+        | Command::Config { .. }
+        | Command::Completions { .. }
+        | Command::Help => DEFAULT_WORKSPACE.to_string(),
+        Command::Complete { ref words } => {
+            active_workspace(completion::workspace_flag(words).or(workspace_flag))
+        }
+        _ => active_workspace(workspace_flag),
+    };
+    // The shell opens the workspace itself, so `use` can switch to another one:
+    if let Command::Shell = command_line.command {
+        return run_shell(&workspace);
+    }
+    let conn = open_workspace(&workspace)?;
+    before_command(&conn, &command_line.command);
+    run_command(&conn, command_line)
+}
+
+pub fn run_command(conn: &Connection, command_line: CommandLine) -> Result<String, CliError> {
+    let CommandLine { filter, command } = command_line;
+    match command {
+        Command::Add {
+            title,
+            modifications,
+        } => {
             if title.is_empty() {
-                return Err(CliError::InvalidCommandArguments(
+                Err(CliError::InvalidCommandArguments(
                     "Task name missing, please enter a name.".to_string(),
-                ));
+                ))
             } else {
-                Ok(create_task(conn, title)?)
+                Ok(create_task(conn, title, &modifications)?)
             }
         }
 
-        Commands::Show { task_id } => {
+        Command::Show { task_id, archived } => {
+            if archived || filter.is_some() {
+                let filter = filter.unwrap_or(Filter::All).search(conn)?;
+                return Ok(read_tasks(conn, &filter, archived)?);
+            }
             let task_id = match task_id {
                 Some(task_id) => Some(parse_num(task_id)?),
                 None => None,
@@ -59,47 +208,234 @@ fn parse_cli(conn: &Connection, args: Cli) -> Result<String, CliError> {
             Ok(read_task(conn, task_id)?)
         }
 
-        Commands::Start { task_id } => {
-            Ok(update_task(conn, parse_num(task_id)?, TaskState::Started)?)
+        Command::Info { target } => {
+            for_each_task(task_ids(conn, filter, target)?, |id| read_task(conn, Some(id)))
+        }
+
+        Command::Modify {
+            target,
+            modifications,
+        } => for_each_task(task_ids(conn, filter, target)?, |id| {
+            modify_task(conn, id, &modifications)
+        }),
+
+        Command::SetState { target, state } => {
+            for_each_task(task_ids(conn, filter, target)?, |id| {
+                update_task(conn, id, state.clone())
+            })
+        }
+
+        Command::Delete { target } => {
+            let ids = task_ids(conn, filter, target)?;
+            confirm_delete(conn, &ids)?;
+            for_each_task(ids, |id| delete_task(conn, id))
+        }
+
+        Command::Notes { target } => {
+            for_each_task(task_ids(conn, filter, target)?, |id| edit_notes(conn, id))
+        }
+
+        Command::Annotate { target, text } => {
+            if text.trim().is_empty() {
+                Err(CliError::InvalidCommandArguments(
+                    "Annotation missing, please enter a text.".to_string(),
+                ))
+            } else {
+                for_each_task(task_ids(conn, filter, target)?, |id| {
+                    annotate_task(conn, id, text.trim())
+                })
+            }
         }
 
-        Commands::Block { task_id } => {
-            Ok(update_task(conn, parse_num(task_id)?, TaskState::Blocked)?)
+        Command::Export { format, columns } => {
+            let filter = filter.unwrap_or(Filter::All).search(conn)?;
+            Ok(export_tasks(conn, format, &filter, &columns)?)
         }
 
-        Commands::Someday { task_id } => {
-            Ok(update_task(conn, parse_num(task_id)?, TaskState::Someday)?)
+        Command::Import { file, from } => Ok(import_tasks(conn, &file, from)?),
+
+        Command::SyncCaldav => match &CONFIG.caldav {
+            Some(caldav) => Ok(sync_caldav(conn, caldav)?),
+            None => Err(SyncError::NotConfigured(
+                "Add a [caldav] section with url, username & password to config.toml."
+                    .to_string(),
+            ))?,
+        },
+
+        Command::Backup { path } => Ok(backup_database(conn, path)?),
+
+        Command::Restore { file } => Ok(restore_database(conn, &file)?),
+
+        Command::Archive { older_than } => {
+            Ok(archive_tasks(conn, parse_older_than(older_than)?)?)
         }
 
-        Commands::Cancel { task_id } => Ok(update_task(
-            conn,
-            parse_num(task_id)?,
-            TaskState::Cancelled,
-        )?),
+        Command::Trash { action } => match action {
+            None => Ok(read_trash(conn)?),
+            Some(TrashAction::Empty { older_than }) => {
+                Ok(empty_trash(conn, parse_older_than(older_than)?)?)
+            }
+        },
+
+        Command::RestoreTask { uuid } => Ok(restore_task(conn, &uuid)?),
+
+        Command::Search { text } => Ok(read_search(conn, &text)?),
+
+        Command::Workspace { action } => match action {
+            WorkspaceAction::List => Ok(list_workspaces()?),
+            WorkspaceAction::Create { name } => Ok(create_workspace(&name)?),
+            WorkspaceAction::Switch { name } => Ok(switch_workspace(&name)?),
+        },
 
-        Commands::Pause { task_id } => {
-            Ok(update_task(conn, parse_num(task_id)?, TaskState::Paused)?)
+        Command::Config { action } => match action {
+            ConfigAction::List { show_origin } => Ok(list_settings(show_origin)?),
+            ConfigAction::Get { key } => Ok(get_setting(&key)?),
+            ConfigAction::Set { key, value } => Ok(set_setting(&key, &value)?),
+            ConfigAction::Validate => Ok(validate_config()?),
+            ConfigAction::DetectFont => Ok(Config::detect_font()?),
+        },
+
+        Command::Report { name } => {
+            // The parser only takes names of reports that exist:
+            let report = &CONFIG.report[&name];
+            let report_filter = match &report.filter {
+                Some(report_filter) => Filter::parse(report_filter)?,
+                None => Filter::All,
+            };
+            let filter = match filter {
+                Some(filter) => Filter::And(Box::new(report_filter), Box::new(filter)),
+                None => report_filter,
+            };
+            let filter = filter.search(conn)?;
+            let columns = report.columns.as_deref().unwrap_or(&TABLE_COLUMNS);
+            Ok(read_report(conn, &filter, columns, &report.sort)?)
         }
 
-        Commands::Finish { task_id } => {
-            Ok(update_task(conn, parse_num(task_id)?, TaskState::Finished)?)
+        // `pct shell` starts before a workspace is opened (see run_line), this is a shell line:
+        Command::Shell => Err(CliError::InvalidCommandArguments(
+            "You are in the shell already".to_string(),
+        )),
+
+        Command::Completions { shell } => Ok(completion_script(shell)),
+
+        Command::Complete { words } => complete(conn, &words),
+
+        Command::Help => Ok(cli_command().render_help().to_string()),
+    }
+}
+
+// Clap's help with the commands of parser.rs added.
+fn cli_command() -> clap::Command {
+    Cli::command().after_help(commands_help())
+}
+
+// The commands of parser.rs, filters and modifications, for `pct help` and the shell's help.
+pub fn commands_help() -> String {
+    // Long usages get their description on the next line:
+    const WIDTH: usize = 36;
+    let commands: Vec<String> = COMMANDS
+        .iter()
+        .map(|(name, args, about)| match format!("{} {}", name, args) {
+            usage if usage.len() > WIDTH => format!("  {}\n  {:WIDTH$}  {}", usage, "", about),
+            usage => format!("  {:WIDTH$}  {}", usage, about),
+        })
+        .collect();
+    format!(
+        "Commands:\n{}\n\n\
+        Filters:\n  +tag -tag project:Home state:started priority:H due.before:fri 3-5 text:~word\n  \
+        combined with and, or, not and parentheses\n\n\
+        Modifications:\n  +tag -tag priority:H due:fri project:Home.Garden title:\"New title\"",
+        commands.join("\n")
+    )
+}
+
+// The task with the given id, every task that matches the filter or the tasks picked from a list.
+fn task_ids(
+    conn: &Connection,
+    filter: Option<Filter>,
+    target: Target,
+) -> Result<Vec<i64>, CliError> {
+    match target {
+        Target::Id(task_id) => Ok(vec![parse_num(task_id)?]),
+        Target::Filter => {
+            let filter = filter.unwrap_or(Filter::All).search(conn)?;
+            let tasks = get_tasks_or_empty(conn)?;
+            let ids: Vec<i64> = filter.apply(&tasks, &tasks).iter().map(|task| task.id).collect();
+            if ids.is_empty() {
+                let message = "No task matches the filter".to_string();
+                return Err(CrudError::TaskNotFound(message).into());
+            }
+            Ok(ids)
         }
+        Target::Pick { command } => Ok(pick_tasks(conn, &command)?),
+    }
+}
 
-        Commands::Delete { task_id } => Ok(delete_task(conn, parse_num(task_id)?)?),
+// Run a command on each of the tasks. If one fails, the tasks before it are changed already, so
+// their results are printed before the error.
+fn for_each_task(
+    mut ids: Vec<i64>,
+    mut run: impl FnMut(i64) -> Result<String, CrudError>,
+) -> Result<String, CliError> {
+    // Highest ids first, deleting a task renumbers the ones after it:
+    ids.sort_unstable_by(|a, b| b.cmp(a));
+    let mut results = Vec::new();
+    for id in ids {
+        match run(id) {
+            Ok(result) => results.push(result),
+            Err(err) => {
+                if !results.is_empty() {
+                    results.reverse();
+                    println!("{}", color::stdout(results.join("\n")));
+                }
+                return Err(err.into());
+            }
+        }
     }
+    results.reverse();
+    Ok(results.join("\n"))
 }
 
 fn parse_num(num_string: String) -> Result<i64, CliError> {
     Ok(num_string.parse::<i64>()?)
 }
+
+// "--older-than 30d" is the point in time 30 days ago:
+fn parse_older_than(older_than: Option<String>) -> Result<Option<DateTime<Utc>>, CliError> {
+    match older_than {
+        Some(older_than) => match parse_duration(&older_than) {
+            Some(duration) => Ok(Some(Utc::now() - duration)),
+            None => Err(CliError::InvalidCommandArguments(format!(
+                "Invalid duration '{}', use e.g. 30d, 12h or 2w.",
+                older_than
+            ))),
+        },
+        None => Ok(None),
+    }
+}
 #[cfg(test)]
 #[test]
+#[allow(clippy::vec_init_then_push)]
 fn test_commands() {
+    use crate::error::{ParseError, PromptError};
+    crate::config::use_test_config();
+    prompt::init(InputMode {
+        yes: false,
+        no_input: true,
+    });
+
     let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
-    crate::database::create_table(&conn);
+    crate::database::create_table(&conn).expect("Failed to create tables");
 
+    let run_args = |arg: Vec<&str>| -> Result<String, CliError> {
+        let args = Cli::parse_from(arg);
+        run_command(&conn, parse(&args.args)?)
+    };
     let mut args_to_test = Vec::new();
 
+    // A report without tasks isn't an error:
+    args_to_test.push((vec!["pct", "started"], Ok("No tasks in this report")));
+
     args_to_test.push((
         vec!["pct", "add", "Clean room"],
         Ok("Added new task:\n\u{f096}  \u{1b}[1;34mClean room\u{1b}[0m (#1)"),
@@ -113,8 +449,15 @@ fn test_commands() {
     ));
 
     args_to_test.push((
-        vec!["pct", "show", "1"],
-        Ok("╭────┬────────────┬────╮\n│ 📝 │ \u{1b}[1;34mTask\u{1b}[0m       │ \u{1b}[1;34mID\u{1b}[0m │\n├────┼────────────┼────┤\n│ \u{1b}[37m\u{f096}\u{1b}[0m  │ Clean room │ 1  │\n╰────┴────────────┴────╯")
+        vec!["pct", "annotate", "1", "waiting", "for", "Bob"],
+        Ok("Annotated:\n\u{1b}[37m\u{f096}\u{1b}[0m  \u{1b}[1;34mClean room\u{1b}[0m (#1)\n   waiting for Bob"),
+    ));
+
+    args_to_test.push((
+        vec!["pct", "annotate", "1"],
+        Err(CliError::InvalidCommandArguments(
+            "Annotation missing, please enter a text.".to_string(),
+        )),
     ));
 
     args_to_test.push((
@@ -131,7 +474,7 @@ fn test_commands() {
 
     if let Err(parse_error) = "df".parse::<i64>() {
         args_to_test.push((
-            vec!["pct", "show", "not_valid_number"],
+            vec!["pct", "start", "not_valid_number"],
             Err(CliError::InvalidArgumentFormat(parse_error)),
         ));
     }
@@ -141,6 +484,27 @@ fn test_commands() {
         Ok("Started:\n\u{1b}[33m\u{f044}\u{1b}[0m  \u{1b}[1;34mClean room\u{1b}[0m (#1)"),
     ));
 
+    // [report.started] from the test config:
+    args_to_test.push((
+        vec!["pct", "started"],
+        Ok("╭────┬────────────┬─────────╮\n│ \u{1b}[1;34mID\u{1b}[0m │ \u{1b}[1;34mTask\u{1b}[0m       │ \u{1b}[1;34mUrgency\u{1b}[0m │\n├────┼────────────┼─────────┤\n│ 1  │ Clean room │ 4.0     │\n╰────┴────────────┴─────────╯"),
+    ));
+
+    args_to_test.push((
+        vec!["pct", "nope"],
+        Err(CliError::ParseError(ParseError::command(
+            "Unknown command 'nope', see 'pct help' or add [report.nope] to config.toml.",
+            "pct nope",
+            4,
+            4,
+        ))),
+    ));
+
+    args_to_test.push((
+        vec!["pct", "1", "modify", "+home", "priority:H"],
+        Ok("Modified:\n\u{1b}[33m\u{f044}\u{1b}[0m  \u{1b}[1;34mClean room\u{1b}[0m (#1)"),
+    ));
+
     args_to_test.push((
         vec!["pct", "block", "1"],
         Ok("Blocked:\n\u{1b}[34m\u{f256}\u{1b}[0m  \u{1b}[1;34mClean room\u{1b}[0m (#1)"),
@@ -166,9 +530,14 @@ fn test_commands() {
         Ok("Finished:\n\u{1b}[32m\u{f046}\u{1b}[0m  \u{1b}[1;34mClean room\u{1b}[0m (#1)"),
     ));
 
+    // Nothing may be asked under test, so the deletion can't be confirmed and fails:
     args_to_test.push((
         vec!["pct", "delete", "1"],
-        Ok("Deleted:\n\u{1b}[34mClean room\u{1b}[0m (#1)"),
+        Err(CliError::from(PromptError::InputNeeded(
+            "Delete task 1 'Clean room'? can't be answered here. Add --yes to confirm or set \
+             confirm_destructive = false."
+                .to_string(),
+        ))),
     ));
 
     // Define a custom macro for assertions
@@ -185,6 +554,12 @@ fn test_commands() {
     }
 
     for (arg, expected_result) in args_to_test {
-        assert_result!(parse_cli(&conn, Cli::parse_from(arg)), expected_result);
+        assert_result!(run_args(arg), expected_result);
     }
+
+    // A single task is shown as a card with all its details:
+    let card = run_args(vec!["pct", "+home", "priority:H", "info"]).unwrap();
+    assert!(card.contains("Clean room") && card.contains("\u{f046}\u{1b}[0m Finished"));
+    assert!(card.contains("  waiting for Bob") && card.contains("Started "));
+    assert!(card.contains("home") && card.contains(" H "));
 }
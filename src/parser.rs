@@ -0,0 +1,890 @@
+// parser.rs
+
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use std::path::PathBuf;
+
+use crate::completion::Shell;
+use crate::config::CONFIG;
+use crate::error::ParseError;
+use crate::exchange::Format;
+use crate::filter::{parse_date, Filter};
+use crate::lexer::{tokenize, Token, TokenKind};
+use crate::report::{Column, DEFAULT_COLUMNS};
+use crate::task::TaskState;
+
+// Command line grammar ____________________________________________________________________________
+//
+//   pct [filter] <command> [arguments]
+//
+//   pct +work due.before:fri list      pct 3-5 modify priority:H      pct add Call Bob +phone
+//
+// Everything before the first command name is a filter (see filter.rs). Commands that work on
+// tasks take the tasks matching it, or without a filter the id right after the command.
+#[rustfmt::skip]
+pub const COMMANDS: [(&str, &str, &str); 28] = [
+    ("add", "<title> [modifications]", "Add a task, e.g. `add Call Bob +phone due:fri`"),
+    ("list", "[filter] [--archived]", "Tasks matching the filter"),
+    ("show", "[id | filter] [--archived] [--filter <filter>]", "Tasks, or one task as a card"),
+    ("info", "<id>", "All details of a task, same as `show <id>`"),
+    ("modify", "<id> <modifications>", "Change the title, +tag/-tag, priority:, due:, project:"),
+    ("start", "<id>", "Start a task"),
+    ("block", "<id>", "Block a task"),
+    ("someday", "<id>", "Put a task off to someday"),
+    ("cancel", "<id>", "Cancel a task"),
+    ("pause", "<id>", "Pause a task"),
+    ("finish", "<id>", "Check off a task"),
+    ("delete", "<id>", "Move a task to the trash"),
+    ("notes", "<id>", "Edit the notes of a task with $VISUAL or $EDITOR"),
+    ("annotate", "<id> <text>", "Add a timestamped comment to a task"),
+    ("search", "<text>", "Full-text search in titles, notes and annotations"),
+    ("export", "[--format <format>] [--filter <filter>] [--columns <columns>]", "Export tasks"),
+    ("import", "<file> [--from <format>]", "Import or update tasks from a file"),
+    ("sync", "caldav", "Two-way sync with the CalDAV task list in config.toml"),
+    ("backup", "[path]", "Copy the database, by default into the backups folder"),
+    ("restore", "<file>", "Replace all tasks with the ones from a backup"),
+    ("archive", "[--older-than <duration>]", "Move finished and cancelled tasks out of the list"),
+    ("trash", "[empty [--older-than <duration>]]", "List deleted tasks or delete them for good"),
+    ("restore-task", "<uuid>", "Bring a deleted task back from the trash"),
+    ("workspace", "list | create <name> | switch <name>", "Separate task lists"),
+    ("config", "list | get <key> | set <key> <value> | validate | detect-font", "Settings"),
+    ("shell", "", "Interactive prompt with history and <TAB> completion"),
+    ("completions", "bash | zsh | fish", "Print a shell completion script"),
+    ("help", "", "Show this help"),
+];
+
+pub struct CommandLine {
+    /// The words before the command, `--filter` and the words after a report name
+    pub filter: Option<Filter>,
+    pub command: Command,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Add {
+        title: String,
+        modifications: Vec<Modification>,
+    },
+    Show {
+        task_id: Option<String>,
+        archived: bool,
+    },
+    Info {
+        target: Target,
+    },
+    Modify {
+        target: Target,
+        modifications: Vec<Modification>,
+    },
+    SetState {
+        target: Target,
+        state: TaskState,
+    },
+    Delete {
+        target: Target,
+    },
+    Notes {
+        target: Target,
+    },
+    Annotate {
+        target: Target,
+        text: String,
+    },
+    Search {
+        text: String,
+    },
+    Export {
+        format: Format,
+        columns: Vec<Column>,
+    },
+    Import {
+        file: PathBuf,
+        from: Option<Format>,
+    },
+    SyncCaldav,
+    Backup {
+        path: Option<PathBuf>,
+    },
+    Restore {
+        file: PathBuf,
+    },
+    Archive {
+        older_than: Option<String>,
+    },
+    Trash {
+        action: Option<TrashAction>,
+    },
+    RestoreTask {
+        uuid: String,
+    },
+    Workspace {
+        action: WorkspaceAction,
+    },
+    Config {
+        action: ConfigAction,
+    },
+    /// Interactive prompt, see shell.rs
+    Shell,
+    Completions {
+        shell: Shell,
+    },
+    /// Candidates for the word being completed, see completion.rs
+    Complete {
+        words: Vec<String>,
+    },
+    /// Named report from config.toml, e.g. `pct today` for [report.today]
+    Report {
+        name: String,
+    },
+    Help,
+}
+
+impl Command {
+    // Commands that change tasks, the daily backup is made before the first of them.
+    pub fn changes_tasks(&self) -> bool {
+        matches!(
+            self,
+            Command::Add { .. }
+                | Command::Modify { .. }
+                | Command::SetState { .. }
+                | Command::Delete { .. }
+                | Command::Notes { .. }
+                | Command::Annotate { .. }
+                | Command::Import { .. }
+                | Command::SyncCaldav
+                | Command::Restore { .. }
+                | Command::Archive { .. }
+                | Command::Trash { action: Some(_) }
+                | Command::RestoreTask { .. }
+        )
+    }
+}
+
+// The tasks a command works on
+#[derive(Debug, PartialEq)]
+pub enum Target {
+    /// `pct finish 3`
+    Id(String),
+    /// The tasks matching the filter, `pct 3-5 finish`
+    Filter,
+    /// Neither was given, so the tasks are picked from a list
+    Pick { command: String },
+}
+
+#[derive(Debug)]
+pub enum TrashAction {
+    /// Delete the tasks in the trash for good, only those deleted before older_than if set
+    Empty { older_than: Option<String> },
+}
+
+#[derive(Debug)]
+pub enum WorkspaceAction {
+    List,
+    Create { name: String },
+    Switch { name: String },
+}
+
+#[derive(Debug)]
+pub enum ConfigAction {
+    List { show_origin: bool },
+    Get { key: String },
+    Set { key: String, value: String },
+    Validate,
+    DetectFont,
+}
+
+// Changes to a task in `add` and `modify`
+#[derive(Debug, Clone)]
+pub enum Modification {
+    Title(String),
+    /// "+tag" adds, "-tag" removes a tag
+    Tag(String, bool),
+    /// "priority:" removes the priority
+    Priority(Option<String>),
+    Due(Option<DateTime<Utc>>),
+    /// A project path like "Work.Website", "project:" takes the task out of its project
+    Project(Option<String>),
+}
+
+pub fn parse(args: &[String]) -> Result<CommandLine, ParseError> {
+    // The hidden `pct __complete <words>` of the completion scripts takes half-typed words as is:
+    if args.first().is_some_and(|arg| arg == "__complete") {
+        return Ok(CommandLine {
+            filter: None,
+            command: Command::Complete {
+                words: args[1..].to_vec(),
+            },
+        });
+    }
+    let (line, tokens) = tokenize(args);
+    let mut parser = Parser {
+        line: &line,
+        tokens: &tokens,
+        position: 0,
+    };
+    parser.command_line()
+}
+
+// Recursive descent parser ________________________________________________________________________
+struct Parser<'a> {
+    line: &'a str,
+    tokens: &'a [Token],
+    position: usize,
+}
+
+#[derive(Default)]
+struct Arguments {
+    words: Vec<Token>,
+    flags: Vec<Flag>,
+}
+
+struct Flag {
+    name: String,
+    value: Option<String>,
+    /// The token errors about the value point at
+    token: Token,
+}
+
+impl Arguments {
+    fn flag(&self, name: &str) -> Option<&Flag> {
+        self.flags.iter().find(|flag| flag.name == name)
+    }
+
+    fn value(&self, name: &str) -> Option<String> {
+        self.flag(name).and_then(|flag| flag.value.clone())
+    }
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    // command_line := filter [command arguments]
+    fn command_line(&mut self) -> Result<CommandLine, ParseError> {
+        let help = TokenKind::Flag("help".to_string(), None);
+        if self.tokens.iter().any(|token| token.kind == help) {
+            return Ok(CommandLine {
+                filter: None,
+                command: Command::Help,
+            });
+        }
+        let filter = self.filter()?;
+        match self.next() {
+            Some(name) => self.command(&name, filter),
+            // Without a command, "pct" shows all tasks and "pct +work" the matching ones:
+            None => Ok(CommandLine {
+                filter,
+                command: Command::Show {
+                    task_id: None,
+                    archived: false,
+                },
+            }),
+        }
+    }
+
+    // filter := token*, up to the first command name
+    fn filter(&mut self) -> Result<Option<Filter>, ParseError> {
+        let start = self.position;
+        while let Some(token) = self.tokens.get(self.position) {
+            if is_command(token) {
+                break;
+            }
+            if let TokenKind::Flag(name, _) = &token.kind {
+                return Err(self.unknown_flag(token, name, None));
+            }
+            self.position += 1;
+        }
+        let words = &self.tokens[start..self.position];
+        // A lone word like "pct nope" is more likely a misspelled command than a title filter:
+        if let (Some(word), None) = (words.first(), self.tokens.get(self.position)) {
+            let keyword = ["and", "or", "not"].contains(&word.text.to_lowercase().as_str());
+            let plain = word
+                .text
+                .chars()
+                .all(|char| char.is_alphabetic() || char == '-');
+            if word.kind == TokenKind::Word && plain && !keyword {
+                return Err(self.error(
+                    word,
+                    &format!(
+                        "Unknown command '{}', see 'pct help' or add [report.{}] to config.toml.",
+                        word.text, word.text
+                    ),
+                ));
+            }
+        }
+        self.parse_filter(words)
+    }
+
+    // command := name arguments
+    fn command(&mut self, name: &Token, filter: Option<Filter>) -> Result<CommandLine, ParseError> {
+        let has_filter = filter.is_some();
+        let mut filter = filter;
+        let command = match name.text.as_str() {
+            "add" => {
+                let args = self.arguments(name, &[])?;
+                let (title, modifications) = self.modifications(&args.words, false)?;
+                Command::Add {
+                    title: title.join(" "),
+                    modifications,
+                }
+            }
+            // Words after `list` and `show` narrow the filter down, like after a report name:
+            "list" => {
+                let args = self.arguments(name, &[("archived", false)])?;
+                filter = and(filter, self.parse_filter(&args.words)?);
+                Command::Show {
+                    task_id: None,
+                    archived: args.flag("archived").is_some(),
+                }
+            }
+            "show" => {
+                let args = self.arguments(name, &[("archived", false), ("filter", true)])?;
+                let archived = args.flag("archived").is_some();
+                let task_id = match args.words.as_slice() {
+                    [word] if word.text.parse::<i64>().is_ok() => Some(word),
+                    _ => None,
+                };
+                match task_id {
+                    Some(task_id) if has_filter || archived || args.flag("filter").is_some() => {
+                        let message = "A task id can't be combined with a filter or --archived";
+                        return Err(self.error(task_id, message));
+                    }
+                    Some(_) => {}
+                    None => filter = and(filter, self.parse_filter(&args.words)?),
+                }
+                filter = self.flag_filter(filter, &args)?;
+                Command::Show {
+                    task_id: task_id.map(|token| token.text.clone()),
+                    archived,
+                }
+            }
+            "info" | "delete" | "notes" | "start" | "block" | "someday" | "cancel" | "pause"
+            | "finish" => {
+                let mut args = self.arguments(name, &[])?;
+                let target = self.target(name, &mut args.words, has_filter, false);
+                self.at_most(&args.words, 0)?;
+                let state = match name.text.as_str() {
+                    "info" => return self.done(filter, Command::Info { target }),
+                    "delete" => return self.done(filter, Command::Delete { target }),
+                    "notes" => return self.done(filter, Command::Notes { target }),
+                    "start" => TaskState::Started,
+                    "block" => TaskState::Blocked,
+                    "someday" => TaskState::Someday,
+                    "cancel" => TaskState::Cancelled,
+                    "pause" => TaskState::Paused,
+                    _ => TaskState::Finished,
+                };
+                Command::SetState { target, state }
+            }
+            "annotate" => {
+                let mut args = self.arguments(name, &[])?;
+                let target = self.target(name, &mut args.words, has_filter, true);
+                Command::Annotate {
+                    target,
+                    text: texts(&args.words).join(" "),
+                }
+            }
+            "modify" => {
+                let mut args = self.arguments(name, &[])?;
+                let target = self.target(name, &mut args.words, has_filter, true);
+                let (title, mut modifications) = self.modifications(&args.words, true)?;
+                if !title.is_empty() {
+                    modifications.push(Modification::Title(title.join(" ")));
+                }
+                if modifications.is_empty() {
+                    return Err(self.missing("Nothing to change, e.g. pct 3 modify priority:H"));
+                }
+                Command::Modify {
+                    target,
+                    modifications,
+                }
+            }
+            "search" => {
+                let args = self.arguments(name, &[])?;
+                Command::Search {
+                    text: texts(&args.words).join(" "),
+                }
+            }
+            "export" => {
+                let flags = [("format", true), ("filter", true), ("columns", true)];
+                let args = self.arguments(name, &flags)?;
+                self.at_most(&args.words, 0)?;
+                filter = self.flag_filter(filter, &args)?;
+                let format = match args.flag("format") {
+                    Some(flag) => self.value_enum(flag, "format")?,
+                    None => Format::Ics,
+                };
+                let columns = match args.flag("columns") {
+                    Some(flag) => self.columns(flag)?,
+                    None => DEFAULT_COLUMNS.to_vec(),
+                };
+                Command::Export { format, columns }
+            }
+            "import" => {
+                let args = self.arguments(name, &[("from", true)])?;
+                let file = self.word(&args.words, 0, "File missing, e.g. pct import tasks.ics")?;
+                self.at_most(&args.words, 1)?;
+                let from = match args.flag("from") {
+                    Some(flag) => Some(self.value_enum(flag, "format")?),
+                    None => None,
+                };
+                Command::Import {
+                    file: PathBuf::from(file),
+                    from,
+                }
+            }
+            "sync" => {
+                let args = self.arguments(name, &[])?;
+                let target = self.word(&args.words, 0, "Sync target missing, use caldav")?;
+                if target != "caldav" {
+                    let message = format!("Unknown sync target '{}', use caldav", target);
+                    return Err(self.error(&args.words[0], &message));
+                }
+                self.at_most(&args.words, 1)?;
+                Command::SyncCaldav
+            }
+            "backup" => {
+                let args = self.arguments(name, &[])?;
+                self.at_most(&args.words, 1)?;
+                Command::Backup {
+                    path: args.words.first().map(|token| PathBuf::from(&token.text)),
+                }
+            }
+            "restore" => {
+                let args = self.arguments(name, &[])?;
+                let file = self.word(&args.words, 0, "Backup file missing")?;
+                self.at_most(&args.words, 1)?;
+                Command::Restore {
+                    file: PathBuf::from(file),
+                }
+            }
+            "archive" => {
+                let args = self.arguments(name, &[("older-than", true)])?;
+                self.at_most(&args.words, 0)?;
+                Command::Archive {
+                    older_than: args.value("older-than"),
+                }
+            }
+            "trash" => {
+                let args = self.arguments(name, &[("older-than", true)])?;
+                self.at_most(&args.words, 1)?;
+                let action = match args.words.first() {
+                    Some(token) if token.text == "empty" => Some(TrashAction::Empty {
+                        older_than: args.value("older-than"),
+                    }),
+                    Some(token) => {
+                        let message = format!("Unknown trash action '{}', use empty", token.text);
+                        return Err(self.error(token, &message));
+                    }
+                    None => match args.flag("older-than") {
+                        Some(flag) => {
+                            let message = "--older-than only works with `trash empty`";
+                            return Err(self.error(&flag.token, message));
+                        }
+                        None => None,
+                    },
+                };
+                Command::Trash { action }
+            }
+            "restore-task" => {
+                let args = self.arguments(name, &[])?;
+                let uuid = self.word(&args.words, 0, "Uuid missing, see pct trash")?;
+                self.at_most(&args.words, 1)?;
+                Command::RestoreTask { uuid }
+            }
+            "workspace" => {
+                let args = self.arguments(name, &[])?;
+                let usage = "use list, create or switch";
+                let action = self.word(&args.words, 0, &format!("Action missing, {}", usage))?;
+                let action = match action.as_str() {
+                    "list" => {
+                        self.at_most(&args.words, 1)?;
+                        WorkspaceAction::List
+                    }
+                    "create" | "switch" => {
+                        let name = self.word(&args.words, 1, "Workspace name missing")?;
+                        self.at_most(&args.words, 2)?;
+                        match action.as_str() {
+                            "create" => WorkspaceAction::Create { name },
+                            _ => WorkspaceAction::Switch { name },
+                        }
+                    }
+                    _ => {
+                        let message = format!("Unknown workspace action '{}', {}", action, usage);
+                        return Err(self.error(&args.words[0], &message));
+                    }
+                };
+                Command::Workspace { action }
+            }
+            "config" => {
+                let args = self.arguments(name, &[("show-origin", false)])?;
+                let usage = "use list, get, set, validate or detect-font";
+                let action = self.word(&args.words, 0, &format!("Action missing, {}", usage))?;
+                if let (Some(flag), false) = (args.flag("show-origin"), action == "list") {
+                    let message = "--show-origin only works with `config list`";
+                    return Err(self.error(&flag.token, message));
+                }
+                let action = match action.as_str() {
+                    "list" => ConfigAction::List {
+                        show_origin: args.flag("show-origin").is_some(),
+                    },
+                    "get" => ConfigAction::Get {
+                        key: self.word(&args.words, 1, "Key missing, e.g. backup.keep_daily")?,
+                    },
+                    "set" => ConfigAction::Set {
+                        key: self.word(&args.words, 1, "Key missing, e.g. theme")?,
+                        value: self.word(&args.words, 2, "Value missing, e.g. light")?,
+                    },
+                    "validate" => ConfigAction::Validate,
+                    "detect-font" => ConfigAction::DetectFont,
+                    _ => {
+                        let message = format!("Unknown config action '{}', {}", action, usage);
+                        return Err(self.error(&args.words[0], &message));
+                    }
+                };
+                let count = match action {
+                    ConfigAction::Get { .. } => 2,
+                    ConfigAction::Set { .. } => 3,
+                    _ => 1,
+                };
+                self.at_most(&args.words, count)?;
+                Command::Config { action }
+            }
+            "shell" => {
+                let args = self.arguments(name, &[])?;
+                self.at_most(&args.words, 0)?;
+                Command::Shell
+            }
+            "completions" => {
+                let args = self.arguments(name, &[])?;
+                self.word(&args.words, 0, "Shell missing, use bash, zsh or fish")?;
+                self.at_most(&args.words, 1)?;
+                Command::Completions {
+                    shell: self.variant(&args.words[0], &args.words[0].text, "shell")?,
+                }
+            }
+            "help" => Command::Help,
+            // Words after a report's name narrow it down further:
+            report => {
+                let args = self.arguments(name, &[])?;
+                filter = and(filter, self.parse_filter(&args.words)?);
+                Command::Report {
+                    name: report.to_string(),
+                }
+            }
+        };
+        self.done(filter, command)
+    }
+
+    fn done(&self, filter: Option<Filter>, command: Command) -> Result<CommandLine, ParseError> {
+        Ok(CommandLine { filter, command })
+    }
+
+    // arguments := (word | flag [value])*
+    fn arguments(
+        &mut self,
+        command: &Token,
+        flags: &[(&str, bool)],
+    ) -> Result<Arguments, ParseError> {
+        let mut arguments = Arguments::default();
+        while let Some(token) = self.next() {
+            let TokenKind::Flag(name, value) = &token.kind else {
+                arguments.words.push(token);
+                continue;
+            };
+            let Some((_, takes_value)) = flags.iter().find(|(flag, _)| flag == name) else {
+                return Err(self.unknown_flag(&token, name, Some(command)));
+            };
+            let (value, value_token) = match (takes_value, value) {
+                (false, None) => (None, token.clone()),
+                (false, Some(_)) => {
+                    return Err(self.error(&token, &format!("--{} takes no value", name)))
+                }
+                (true, Some(value)) => (Some(value.clone()), token.clone()),
+                (true, None) => match self.next() {
+                    Some(value_token) => (Some(value_token.text.clone()), value_token),
+                    None => return Err(self.error(&token, &format!("--{} needs a value", name))),
+                },
+            };
+            arguments.flags.push(Flag {
+                name: name.clone(),
+                value,
+                token: value_token,
+            });
+        }
+        Ok(arguments)
+    }
+
+    // modifications := ("+"tag | "-"tag | attribute:value | word)*
+    // The words are the title. `add` takes any text for it, while `modify` rejects unknown
+    // attributes, which are more likely typos than a new title.
+    fn modifications(
+        &self,
+        words: &[Token],
+        strict: bool,
+    ) -> Result<(Vec<String>, Vec<Modification>), ParseError> {
+        let mut title = Vec::new();
+        let mut modifications = Vec::new();
+        for token in words {
+            match (parse_modification(&token.kind), &token.kind) {
+                (Some(Err(message)), _) => return Err(self.error(token, &message)),
+                (Some(Ok(Modification::Title(text))), _) if !strict => title.push(text),
+                (Some(Ok(Modification::Tag(_, false))), _) if !strict => {
+                    title.push(token.text.clone())
+                }
+                (Some(Ok(modification)), _) => modifications.push(modification),
+                (None, TokenKind::Attribute(key, _)) if strict => {
+                    let message = format!(
+                        "Unknown attribute '{}', use priority, due, project or title",
+                        key
+                    );
+                    return Err(self.error(token, &message));
+                }
+                (None, _) => title.push(token.text.clone()),
+            }
+        }
+        Ok((title, modifications))
+    }
+
+    // The tasks a command works on: those matching the filter before it, otherwise the id after
+    // it. Without either they are picked from a list. Commands that take more words after the id
+    // (`modify`, `annotate`) only take a number as the id.
+    fn target(
+        &self,
+        command: &Token,
+        words: &mut Vec<Token>,
+        has_filter: bool,
+        takes_text: bool,
+    ) -> Target {
+        let is_id = |word: &Token| !takes_text || word.text.parse::<i64>().is_ok();
+        match (has_filter, words.first()) {
+            (true, _) => Target::Filter,
+            (false, Some(word)) if is_id(word) => Target::Id(words.remove(0).text),
+            (false, _) => Target::Pick {
+                command: command.text.clone(),
+            },
+        }
+    }
+
+    // The words of a filter are parsed by filter.rs, errors still point into the command line.
+    fn parse_filter(&self, words: &[Token]) -> Result<Option<Filter>, ParseError> {
+        let (Some(first), Some(last)) = (words.first(), words.last()) else {
+            return Ok(None);
+        };
+        // An empty title filter would match every task, e.g. `pct "" delete`:
+        if let Some(empty) = words.iter().find(|word| word.text.trim().is_empty()) {
+            return Err(self.error(empty, "Empty filter word, please leave it out."));
+        }
+        let length = last.start + last.length - first.start;
+        let text: String = self.line.chars().skip(first.start).take(length).collect();
+        match Filter::parse(&text) {
+            Ok(filter) => Ok(Some(filter)),
+            Err(err) => Err(err.within(self.line, first.start)),
+        }
+    }
+
+    // `--filter` narrows down the filter before the command.
+    fn flag_filter(
+        &self,
+        filter: Option<Filter>,
+        args: &Arguments,
+    ) -> Result<Option<Filter>, ParseError> {
+        let Some(flag) = args.flag("filter") else {
+            return Ok(filter);
+        };
+        let value = flag.value.as_deref().unwrap_or_default();
+        // The value is at the end of its token, which is either "--filter=..." or the value:
+        let offset = flag.token.start + flag.token.length - value.chars().count();
+        match Filter::parse(value) {
+            Ok(flag_filter) => Ok(and(filter, Some(flag_filter))),
+            Err(err) => Err(err.within(self.line, offset)),
+        }
+    }
+
+    fn value_enum<T: ValueEnum>(&self, flag: &Flag, what: &str) -> Result<T, ParseError> {
+        let value = flag.value.as_deref().unwrap_or_default();
+        self.variant(&flag.token, value, what)
+    }
+
+    // "--columns id,title,due"
+    fn columns(&self, flag: &Flag) -> Result<Vec<Column>, ParseError> {
+        let value = flag.value.as_deref().unwrap_or_default();
+        value
+            .split(',')
+            .map(|column| self.variant(&flag.token, column.trim(), "column"))
+            .collect()
+    }
+
+    fn variant<T: ValueEnum>(
+        &self,
+        token: &Token,
+        value: &str,
+        what: &str,
+    ) -> Result<T, ParseError> {
+        T::from_str(value, true).map_err(|_| {
+            let names: Vec<String> = T::value_variants()
+                .iter()
+                .filter_map(|variant| variant.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .collect();
+            let message = format!("Unknown {} '{}', use {}", what, value, names.join(", "));
+            self.error(token, &message)
+        })
+    }
+
+    fn word(&self, words: &[Token], index: usize, missing: &str) -> Result<String, ParseError> {
+        match words.get(index) {
+            Some(token) => Ok(token.text.clone()),
+            None => Err(self.missing(missing)),
+        }
+    }
+
+    fn at_most(&self, words: &[Token], count: usize) -> Result<(), ParseError> {
+        match words.get(count) {
+            Some(token) => Err(self.error(token, &format!("Unexpected argument '{}'", token.text))),
+            None => Ok(()),
+        }
+    }
+
+    fn unknown_flag(&self, token: &Token, name: &str, command: Option<&Token>) -> ParseError {
+        let message = match (name, command) {
+            ("workspace" | "color" | "config" | "yes" | "no-input", _) => {
+                format!(
+                    "--{} goes before the filter and command, e.g. pct --{} ...",
+                    name, name
+                )
+            }
+            (_, Some(command)) => format!("Unknown option --{} for '{}'", name, command.text),
+            (_, None) => "Options go after the command".to_string(),
+        };
+        self.error(token, &message)
+    }
+
+    fn error(&self, token: &Token, message: &str) -> ParseError {
+        ParseError::command(message, self.line, token.start, token.length)
+    }
+
+    // Points right behind the end of the command line.
+    fn missing(&self, message: &str) -> ParseError {
+        ParseError::command(message, self.line, self.line.chars().count() + 1, 1)
+    }
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn is_command(token: &Token) -> bool {
+    token.kind == TokenKind::Word && is_command_name(&token.text)
+}
+
+pub fn is_command_name(name: &str) -> bool {
+    COMMANDS.iter().any(|(command, _, _)| *command == name) || CONFIG.report.contains_key(name)
+}
+
+fn and(filter: Option<Filter>, other: Option<Filter>) -> Option<Filter> {
+    match (filter, other) {
+        (Some(filter), Some(other)) => Some(Filter::And(Box::new(filter), Box::new(other))),
+        (filter, other) => filter.or(other),
+    }
+}
+
+fn texts(words: &[Token]) -> Vec<String> {
+    words.iter().map(|token| token.text.clone()).collect()
+}
+
+// "+tag", "priority:H", "due:fri", "project:Home.Garden" ... and None for anything else.
+fn parse_modification(kind: &TokenKind) -> Option<Result<Modification, String>> {
+    let (key, value) = match kind {
+        TokenKind::Tag(tag, add) => return Some(Ok(Modification::Tag(tag.clone(), *add))),
+        TokenKind::Attribute(key, value) => (key.as_str(), value.as_str()),
+        _ => return None,
+    };
+    Some(match key {
+        "priority" | "pri" => match value.to_uppercase().as_str() {
+            "" => Ok(Modification::Priority(None)),
+            priority @ ("H" | "M" | "L") => Ok(Modification::Priority(Some(priority.to_string()))),
+            _ => Err(format!("Unknown priority '{}', use H, M or L", value)),
+        },
+        "due" => match (value, parse_date(value)) {
+            ("" | "none", _) => Ok(Modification::Due(None)),
+            (_, Some((start, _))) => Ok(Modification::Due(Some(start))),
+            (_, None) => Err(format!("Invalid date '{}'", value)),
+        },
+        "project" | "pro" => Ok(Modification::Project(
+            Some(value.to_string()).filter(|project| !project.is_empty()),
+        )),
+        "title" | "description" => match value.trim() {
+            "" => Err("The title can't be empty".to_string()),
+            title => Ok(Modification::Title(title.to_string())),
+        },
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+#[test]
+fn test_parser() {
+    crate::config::use_test_config();
+    let parse = |line: &str| parse(&line.split(' ').map(String::from).collect::<Vec<_>>());
+
+    let command_line = parse("+work due.before:fri list").unwrap();
+    assert!(command_line.filter.is_some());
+    assert!(matches!(
+        command_line.command,
+        Command::Show {
+            task_id: None,
+            archived: false
+        }
+    ));
+
+    let command_line = parse("3-5 modify priority:H -home Paint fence").unwrap();
+    let Command::Modify {
+        target: Target::Filter,
+        modifications,
+    } = command_line.command
+    else {
+        panic!("Expected a modify command");
+    };
+    assert!(matches!(modifications[0], Modification::Priority(Some(_))));
+    assert!(matches!(&modifications[1], Modification::Tag(tag, false) if tag == "home"));
+    assert!(matches!(&modifications[2], Modification::Title(title) if title == "Paint fence"));
+
+    let command_line = parse("add Re: budget +finance due:tomorrow").unwrap();
+    let Command::Add {
+        title,
+        modifications,
+    } = command_line.command
+    else {
+        panic!("Expected an add command");
+    };
+    assert_eq!((title.as_str(), modifications.len()), ("Re: budget", 2));
+    assert!(parse("show +home").unwrap().filter.is_some());
+    assert!(matches!(
+        parse("start 4").unwrap().command,
+        Command::SetState { .. }
+    ));
+    assert!(matches!(
+        parse("started +home").unwrap().command,
+        Command::Report { .. }
+    ));
+
+    // Errors point at the offending token:
+    let error = parse("+work frobnicate:x list").err().unwrap().to_string();
+    assert!(error.contains("Invalid Filter: Unknown filter 'frobnicate'"));
+    assert!(error.ends_with("\n  pct +work frobnicate:x list\n            ^^^^^^^^^^^^"));
+    let error = parse("3 modify priorty:H").err().unwrap().to_string();
+    assert!(error.contains("Invalid Command: Unknown attribute 'priorty'"));
+    assert!(error.ends_with("\n  pct 3 modify priorty:H\n               ^^^^^^^^^"));
+    let error = parse("export --format pdf").err().unwrap().to_string();
+    assert!(error.ends_with("\n  pct export --format pdf\n                      ^^^"));
+    assert!(matches!(
+        parse("modify priority:L").unwrap().command,
+        Command::Modify { target: Target::Pick { .. }, .. }
+    ));
+    assert!(parse("start 3 4").is_err());
+    assert!(parse("list --workspace home").is_err());
+    let error = parse(" delete").err().unwrap().to_string();
+    assert!(error.ends_with("\n  pct \"\" delete\n      ^^"));
+    assert!(parse("show  +home").is_err());
+}
@@ -0,0 +1,109 @@
+// paths.rs
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::CONFIG;
+use crate::error::{ConfigError, DatabaseError};
+
+// Where pocato keeps its files ____________________________________________________________________
+//
+//   config:   $POCATO_DIR  >  $XDG_CONFIG_HOME/pocato  >  ~/.config/pocato (%APPDATA%\pocato)
+//   system:   /etc/pocato/config.toml (%PROGRAMDATA%\pocato\config.toml), read before the above
+//   data:     $POCATO_DATA_DIR  >  database_path in config.toml  >  $XDG_DATA_HOME/pocato
+//             >  $POCATO_DIR/data  >  ~/.local/share/pocato (platform data dir)
+//             A tasks.db in the platform data dir is kept there, see pocato_dir_data()
+//   runtime:  $XDG_RUNTIME_DIR/pocato  >  <temp dir>/pocato-<user>
+//
+// Empty variables count as unset, just like XDG asks for.
+pub fn config_dir() -> Result<PathBuf, ConfigError> {
+    if let Some(pocato_dir) = env_path("POCATO_DIR") {
+        return Ok(pocato_dir);
+    }
+    if let Some(config_home) = env_path("XDG_CONFIG_HOME") {
+        return Ok(config_home.join("pocato"));
+    }
+    // Linux and MacOS config directory
+    #[cfg(not(target_os = "windows"))]
+    let config_home = PathBuf::from(env::var("HOME")?).join(".config");
+
+    // Windows config directory
+    #[cfg(target_os = "windows")]
+    let config_home = PathBuf::from(env::var("APPDATA")?);
+
+    Ok(config_home.join("pocato"))
+}
+
+// Settings for every user of the machine, which their own config.toml can override.
+pub fn system_config_file() -> Option<PathBuf> {
+    #[cfg(not(target_os = "windows"))]
+    return Some(PathBuf::from("/etc/pocato/config.toml"));
+
+    #[cfg(target_os = "windows")]
+    return env_path("PROGRAMDATA").map(|program_data| program_data.join("pocato/config.toml"));
+}
+
+// The folder with tasks.db, the workspaces and backups. It is created if it doesn't exist.
+pub fn data_dir() -> Result<PathBuf, DatabaseError> {
+    let data_dir = env_path("POCATO_DATA_DIR")
+        .or_else(|| CONFIG.database_path.clone())
+        .or_else(|| env_path("XDG_DATA_HOME").map(|data_home| data_home.join("pocato")))
+        .or_else(pocato_dir_data)
+        .or_else(|| platform_data_dir().map(|data_home| data_home.join("pocato")))
+        .ok_or_else(|| {
+            DatabaseError::NoDataDir(
+                "Can't find a folder for the task database, please set $POCATO_DATA_DIR or \
+                database_path in config.toml."
+                    .to_string(),
+            )
+        })?;
+
+    fs::create_dir_all(&data_dir)
+        .map_err(|err| DatabaseError::Inaccessible(data_dir.clone(), err))?;
+    Ok(data_dir)
+}
+
+// Short-lived files like locks, which don't need to survive a reboot.
+pub fn runtime_dir() -> PathBuf {
+    match env_path("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) => runtime_dir.join("pocato"),
+        None => {
+            let user = env::var("USER")
+                .or_else(|_| env::var("USERNAME"))
+                .unwrap_or_default();
+            env::temp_dir().join(format!("pocato-{}", user))
+        }
+    }
+}
+
+// Helper functions --------------------------------------------------------------------------------
+
+// Before $POCATO_DIR/data, the tasks always lived in the platform data dir. If they still do, that
+// stays the data dir, so setting $POCATO_DIR doesn't start over with an empty task list.
+fn pocato_dir_data() -> Option<PathBuf> {
+    let data_dir = env_path("POCATO_DIR")?.join("data");
+    let legacy_dir = platform_data_dir().map(|data_home| data_home.join("pocato"));
+    match legacy_dir {
+        Some(legacy_dir)
+            if legacy_dir.join("tasks.db").is_file() && !data_dir.join("tasks.db").is_file() =>
+        {
+            None
+        }
+        _ => Some(data_dir),
+    }
+}
+
+fn platform_data_dir() -> Option<PathBuf> {
+    #[cfg(not(target_os = "windows"))]
+    return dirs::data_dir();
+
+    #[cfg(target_os = "windows")]
+    return dirs::data_local_dir();
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
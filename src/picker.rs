@@ -0,0 +1,117 @@
+// picker.rs
+
+use colored::Colorize;
+use dialoguer::FuzzySelect;
+use rusqlite::Connection;
+
+use crate::crud::get_tasks_or_empty;
+use crate::error::{CliError, CrudError, PromptError};
+use crate::prompt;
+use crate::task::Task;
+
+// Task picker _____________________________________________________________________________________
+//
+// `pct finish` without an id or filter lists the open tasks to pick from, typing narrows the list
+// down. Commands that work on several tasks at once keep asking until "Done" is picked. Without a
+// terminal there is nobody to ask, so the id has to be on the command line.
+const SINGLE_TASK_COMMANDS: [&str; 3] = ["info", "notes", "annotate"];
+
+pub fn pick_tasks(conn: &Connection, command: &str) -> Result<Vec<i64>, CliError> {
+    if !prompt::can_prompt() {
+        return Err(PromptError::InputNeeded(format!(
+            "Which task? Enter its id, e.g. pct {} 3 or pct 3 {}",
+            command, command
+        ))
+        .into());
+    }
+    let mut tasks = open_tasks(conn)?;
+    tasks.sort_by_key(|task| task.id);
+    if tasks.is_empty() {
+        return Err(CrudError::TaskNotFound("There are no open tasks".to_string()).into());
+    }
+    let labels: Vec<String> = tasks.iter().map(label).collect();
+
+    if SINGLE_TASK_COMMANDS.contains(&command) {
+        let prompt = format!(
+            "Pick a task for '{}' (type to search, Esc to cancel)",
+            command
+        );
+        return match select(&prompt, &labels)? {
+            Some(index) => Ok(vec![tasks[index].id]),
+            None => Err(nothing_picked()),
+        };
+    }
+    let mut picked: Vec<usize> = Vec::new();
+    loop {
+        let remaining: Vec<usize> = (0..tasks.len())
+            .filter(|index| !picked.contains(index))
+            .collect();
+        if remaining.is_empty() {
+            break;
+        }
+        // Once a task is picked, the first entry finishes picking:
+        let done = format!("Done, {} {} picked", picked.len(), plural(picked.len()));
+        let offset = usize::from(!picked.is_empty());
+        let items: Vec<String> = (offset == 1)
+            .then_some(done)
+            .into_iter()
+            .chain(remaining.iter().map(|index| labels[*index].clone()))
+            .collect();
+        let prompt = match picked.len() {
+            0 => format!(
+                "Pick tasks for '{}' (type to search, Esc to cancel)",
+                command
+            ),
+            _ => "Another task, or Done".to_string(),
+        };
+        match select(&prompt, &items)? {
+            None => return Err(nothing_picked()),
+            Some(index) if index < offset => break,
+            Some(index) => picked.push(remaining[index - offset]),
+        }
+    }
+    Ok(picked.into_iter().map(|index| tasks[index].id).collect())
+}
+
+// Helper functions --------------------------------------------------------------------------------
+// A terminal that can't show the picker is no answer either, the id is needed then.
+fn select(prompt: &str, items: &[String]) -> Result<Option<usize>, PromptError> {
+    FuzzySelect::new()
+        .with_prompt(prompt)
+        .items(items)
+        .default(0)
+        .interact_opt()
+        .map_err(|err| {
+            PromptError::InputNeeded(format!("The task picker failed, please enter an id: {}", err))
+        })
+}
+
+fn open_tasks(conn: &Connection) -> Result<Vec<Task>, CrudError> {
+    let tasks = get_tasks_or_empty(conn)?;
+    Ok(tasks
+        .into_iter()
+        .filter(|task| !task.state.is_closed())
+        .collect())
+}
+
+// Plain icons, colors would get in the way of the search highlighting.
+fn label(task: &Task) -> String {
+    format!(
+        "{}  {} (#{})",
+        task.state.get_icon().clear(),
+        task.title,
+        task.id
+    )
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        "task"
+    } else {
+        "tasks"
+    }
+}
+
+fn nothing_picked() -> CliError {
+    PromptError::Refused("No task picked, nothing changed.".to_string()).into()
+}
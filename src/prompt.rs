@@ -0,0 +1,66 @@
+// prompt.rs
+
+use dialoguer::Confirm;
+use std::cell::Cell;
+use std::io::IsTerminal;
+
+use crate::config::CONFIG;
+use crate::error::PromptError;
+
+// Questions & confirmations _______________________________________________________________________
+//
+// Deleting tasks and emptying the trash ask first. Scripts can't answer, so they pass --yes, turn
+// confirmations off with `confirm_destructive = false` or, with --no-input, get an error instead of
+// any question. Either way a question that can't be asked fails rather than being taken as "no".
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InputMode {
+    /// --yes: every confirmation is answered with yes
+    pub yes: bool,
+    /// --no-input: nothing is asked, not even which task to pick
+    pub no_input: bool,
+}
+
+// Per thread, so tests running side by side can answer differently.
+thread_local! {
+    static INPUT_MODE: Cell<InputMode> = Cell::new(InputMode::default());
+}
+
+// Call before anything is asked.
+pub fn init(mode: InputMode) {
+    INPUT_MODE.with(|input_mode| input_mode.set(mode));
+}
+
+// Questions are drawn on stderr and answered on stdin.
+pub fn can_prompt() -> bool {
+    !input_mode().no_input && std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+// Ok if the action may go ahead, otherwise why not.
+pub fn confirm(question: &str) -> Result<(), PromptError> {
+    if input_mode().yes || !CONFIG.confirm_destructive.unwrap_or(true) {
+        return Ok(());
+    }
+    if !can_prompt() {
+        return Err(input_needed(question));
+    }
+    match Confirm::new().with_prompt(question).interact() {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(PromptError::Refused(format!(
+            "{} No, nothing changed.",
+            question
+        ))),
+        Err(_) => Err(input_needed(question)),
+    }
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn input_mode() -> InputMode {
+    INPUT_MODE.with(Cell::get)
+}
+
+fn input_needed(question: &str) -> PromptError {
+    PromptError::InputNeeded(format!(
+        "{} can't be answered here. Add --yes to confirm or set confirm_destructive = false.",
+        question
+    ))
+}
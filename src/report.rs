@@ -0,0 +1,380 @@
+// report.rs
+
+use chrono::prelude::*;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use tabled::{builder::Builder, settings::Style};
+
+use crate::task::{Task, TaskState};
+use crate::theme::THEME;
+
+// Columns of task tables, CSV and Markdown reports ________________________________________________
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+    Id,
+    State,
+    Title,
+    Project,
+    Parent,
+    Tags,
+    Due,
+    Priority,
+    Depends,
+    Created,
+    Ended,
+    Uuid,
+    Urgency,
+}
+
+// `pct show` and reports without columns in config.toml:
+pub const TABLE_COLUMNS: [Column; 3] = [Column::State, Column::Title, Column::Id];
+
+pub const DEFAULT_COLUMNS: [Column; 7] = [
+    Column::Id,
+    Column::State,
+    Column::Title,
+    Column::Project,
+    Column::Due,
+    Column::Priority,
+    Column::Tags,
+];
+
+impl Column {
+    fn name(&self) -> &'static str {
+        match self {
+            Column::Id => "ID",
+            Column::State => "State",
+            Column::Title => "Task",
+            Column::Project => "Project",
+            Column::Parent => "Parent",
+            Column::Tags => "Tags",
+            Column::Due => "Due",
+            Column::Priority => "Priority",
+            Column::Depends => "Depends",
+            Column::Created => "Created",
+            Column::Ended => "Ended",
+            Column::Uuid => "UUID",
+            Column::Urgency => "Urgency",
+        }
+    }
+
+    fn header(&self) -> String {
+        match self {
+            Column::State => "📝".to_string(),
+            column => THEME.header(column.name()),
+        }
+    }
+
+    // Table cells show the state as an icon, everything else like in CSV reports.
+    fn cell(&self, task: &Task, by_uuid: &HashMap<&str, &Task>) -> String {
+        match self {
+            Column::State => task.state.get_icon().to_string(),
+            column => column.value(task, by_uuid),
+        }
+    }
+
+    // Plain text without colors or icons, ready for spreadsheets and wiki pages.
+    fn value(&self, task: &Task, by_uuid: &HashMap<&str, &Task>) -> String {
+        match self {
+            Column::Id => task.id.to_string(),
+            Column::State => task.state.to_string(),
+            Column::Title => task.title.clone(),
+            Column::Project => task.project_path(by_uuid).unwrap_or_default(),
+            Column::Parent => match by_uuid.get(task.parent.as_str()) {
+                Some(parent) => parent.title.clone(),
+                None => task.parent.clone(),
+            },
+            Column::Tags => task.tags.join(" "),
+            Column::Due => task.due_utc().map(format_date).unwrap_or_default(),
+            Column::Priority => task.priority.clone().unwrap_or_default(),
+            Column::Depends => task
+                .depends
+                .iter()
+                .map(|uuid| match by_uuid.get(uuid.as_str()) {
+                    Some(task) => task.id.to_string(),
+                    None => uuid.clone(),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            Column::Created => task.created_utc().map(format_date).unwrap_or_default(),
+            Column::Ended => task.ended_utc().map(format_date).unwrap_or_default(),
+            Column::Uuid => task.uuid.clone(),
+            Column::Urgency => format!("{:.1}", urgency(task, by_uuid)),
+        }
+    }
+
+    fn compare(&self, a: &Task, b: &Task, by_uuid: &HashMap<&str, &Task>) -> Ordering {
+        // Tasks without a date come last, as if it was far in the future:
+        let date = |date: Option<DateTime<Utc>>| date.unwrap_or(DateTime::<Utc>::MAX_UTC);
+        match self {
+            Column::Id => a.id.cmp(&b.id),
+            Column::Due => date(a.due_utc()).cmp(&date(b.due_utc())),
+            Column::Created => date(a.created_utc()).cmp(&date(b.created_utc())),
+            Column::Ended => date(a.ended_utc()).cmp(&date(b.ended_utc())),
+            Column::Priority => priority_rank(a).cmp(&priority_rank(b)),
+            Column::Urgency => urgency(a, by_uuid).total_cmp(&urgency(b, by_uuid)),
+            column => column
+                .value(a, by_uuid)
+                .to_lowercase()
+                .cmp(&column.value(b, by_uuid).to_lowercase()),
+        }
+    }
+}
+
+// Sort order of reports, e.g. "urgency-" (descending) or "id+" (ascending, the default) ---------
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SortKey {
+    pub column: Column,
+    pub descending: bool,
+}
+
+impl TryFrom<String> for SortKey {
+    type Error = String;
+
+    fn try_from(key: String) -> Result<Self, Self::Error> {
+        let (name, descending) = match key.strip_suffix('-') {
+            Some(name) => (name, true),
+            None => (key.strip_suffix('+').unwrap_or(&key), false),
+        };
+        match Column::from_str(name, true) {
+            Ok(column) => Ok(SortKey { column, descending }),
+            Err(_) => Err(format!("Invalid sort key '{}', use e.g. \"due+\" or \"urgency-\"", key)),
+        }
+    }
+}
+
+impl From<SortKey> for String {
+    fn from(key: SortKey) -> Self {
+        let name = key.column.to_possible_value().map(|value| value.get_name().to_string());
+        let order = if key.descending { "-" } else { "+" };
+        format!("{}{}", name.unwrap_or_default(), order)
+    }
+}
+
+// How pressing a task is, loosely after Taskwarrior: priority, due date, state, tags and age add
+// up. Closed tasks have no urgency.
+pub fn urgency(task: &Task, by_uuid: &HashMap<&str, &Task>) -> f64 {
+    if task.state.is_closed() {
+        return 0.0;
+    }
+    let now = Utc::now();
+    let priority = match task.priority.as_deref() {
+        Some("H") => 6.0,
+        Some("M") => 3.9,
+        Some("L") => 1.8,
+        _ => 0.0,
+    };
+    // From 2.4 two weeks ahead up to 12 when a week overdue:
+    let due = match task.due_utc() {
+        Some(due) => {
+            let days_overdue = (now - due).num_seconds() as f64 / 86_400.0;
+            12.0 * ((days_overdue + 14.0) * 0.8 / 21.0 + 0.2).clamp(0.2, 1.0)
+        }
+        None => 0.0,
+    };
+    let state = match task.state {
+        TaskState::Started => 4.0,
+        TaskState::Blocked => -5.0,
+        TaskState::Someday => -3.0,
+        _ => 0.0,
+    };
+    let tags = (task.tags.len() as f64 * 0.8).min(1.0);
+    let project = if task.project_path(by_uuid).is_some() { 1.0 } else { 0.0 };
+    let age = task
+        .created_utc()
+        .map(|created| ((now - created).num_days() as f64 / 365.0 * 2.0).min(2.0))
+        .unwrap_or_default();
+    priority + due + state + tags + project + age
+}
+
+// Public report interface _________________________________________________________________________
+//
+// `tasks` are the tasks to report on, `all_tasks` are needed to resolve projects and parents.
+pub fn to_table(tasks: &[Task], all_tasks: &[Task], columns: &[Column]) -> String {
+    let by_uuid = index(all_tasks);
+    let mut builder = Builder::default();
+    builder.set_header(columns.iter().map(Column::header));
+    for task in tasks {
+        builder.push_record(columns.iter().map(|column| column.cell(task, &by_uuid)));
+    }
+    builder.build().with(Style::rounded()).to_string()
+}
+
+// Sort by the first key, ties are broken by the following ones.
+pub fn sort_tasks(tasks: &mut [Task], all_tasks: &[Task], keys: &[SortKey]) {
+    let by_uuid = index(all_tasks);
+    tasks.sort_by(|a, b| {
+        keys.iter()
+            .map(|key| match key.descending {
+                true => key.column.compare(b, a, &by_uuid),
+                false => key.column.compare(a, b, &by_uuid),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+pub fn to_csv(tasks: &[Task], all_tasks: &[Task], columns: &[Column]) -> String {
+    let by_uuid = index(all_tasks);
+    let mut lines = vec![columns
+        .iter()
+        .map(|column| escape_csv(column.name()))
+        .collect::<Vec<_>>()
+        .join(",")];
+    for task in tasks.iter().filter(|task| !task.project) {
+        let values: Vec<String> = columns
+            .iter()
+            .map(|column| escape_csv(&column.value(task, &by_uuid)))
+            .collect();
+        lines.push(values.join(","));
+    }
+    // RFC 4180 wants CRLF line breaks:
+    lines.join("\r\n")
+}
+
+// GitHub task list, one section per project:
+//
+//   ## Home.Garden
+//
+//   - [ ] Plant tomatoes *(started)* — Due: 2026-10-20
+//   - [x] Water the plants
+pub fn to_markdown(tasks: &[Task], all_tasks: &[Task], columns: &[Column]) -> String {
+    let by_uuid = index(all_tasks);
+    let mut projects: BTreeMap<String, Vec<&Task>> = BTreeMap::new();
+    for task in tasks.iter().filter(|task| !task.project) {
+        let project = task.project_path(&by_uuid).unwrap_or_default();
+        projects.entry(project).or_default().push(task);
+    }
+    // Checkbox and title are always there, the project is the heading:
+    let details: Vec<&Column> = columns
+        .iter()
+        .filter(|column| !matches!(column, Column::State | Column::Title | Column::Project))
+        .collect();
+
+    let mut sections = Vec::new();
+    for (project, tasks) in projects {
+        let heading = match project.is_empty() {
+            true => "## No project".to_string(),
+            false => format!("## {}", escape_markdown(&project)),
+        };
+        let mut lines = vec![heading, String::new()];
+        for task in tasks {
+            let mut line = match task.state {
+                TaskState::Finished => format!("- [x] {}", escape_markdown(&task.title)),
+                TaskState::Cancelled => format!("- [x] ~~{}~~", escape_markdown(&task.title)),
+                TaskState::Pending => format!("- [ ] {}", escape_markdown(&task.title)),
+                _ => format!(
+                    "- [ ] {} *({})*",
+                    escape_markdown(&task.title),
+                    task.state.to_string().to_lowercase()
+                ),
+            };
+            let details: Vec<String> = details
+                .iter()
+                .map(|column| (column.name(), column.value(task, &by_uuid)))
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(name, value)| format!("{}: {}", name, escape_markdown(&value)))
+                .collect();
+            if !details.is_empty() {
+                line.push_str(&format!(" — {}", details.join(", ")));
+            }
+            lines.push(line);
+        }
+        sections.push(lines.join("\n"));
+    }
+    sections.join("\n\n")
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn index(tasks: &[Task]) -> HashMap<&str, &Task> {
+    tasks
+        .iter()
+        .map(|task| (task.uuid.as_str(), task))
+        .collect()
+}
+
+// Due dates are usually whole days, which are stored as local midnight.
+fn format_date(date_time: DateTime<Utc>) -> String {
+    let local = date_time.with_timezone(&Local);
+    match local.time() == NaiveTime::MIN {
+        true => local.format("%Y-%m-%d").to_string(),
+        false => local.format("%Y-%m-%d %H:%M").to_string(),
+    }
+}
+
+fn priority_rank(task: &Task) -> u8 {
+    match task.priority.as_deref() {
+        Some("H") => 3,
+        Some("M") => 2,
+        Some("L") => 1,
+        _ => 0,
+    }
+}
+
+fn escape_csv(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+fn escape_markdown(value: &str) -> String {
+    let mut escaped = String::new();
+    for char in value.chars() {
+        if "\\`*_[]<>#~|".contains(char) {
+            escaped.push('\\');
+        }
+        escaped.push(char);
+    }
+    escaped
+}
+
+#[cfg(test)]
+#[test]
+fn test_reports() {
+    let mut home = Task::new("Home".to_string(), 1);
+    home.project = true;
+    let mut fence = Task::new("Paint the fence, white".to_string(), 2);
+    fence.parent = home.uuid.clone();
+    fence.state = TaskState::Started;
+    let mut plants = Task::new("Water the plants".to_string(), 3);
+    plants.parent = home.uuid.clone();
+    plants.state = TaskState::Finished;
+    let mut call = Task::new("Call \"Mom\"".to_string(), 4);
+    call.state = TaskState::Cancelled;
+    call.priority = Some("H".to_string());
+    let tasks = vec![home, fence, plants, call];
+
+    let columns = [Column::Id, Column::Title, Column::Project, Column::Priority];
+    assert_eq!(
+        to_csv(&tasks, &tasks, &columns),
+        "ID,Task,Project,Priority\r\n\
+        2,\"Paint the fence, white\",Home,\r\n\
+        3,Water the plants,Home,\r\n\
+        4,\"Call \"\"Mom\"\"\",,H"
+    );
+    assert_eq!(
+        to_markdown(&tasks, &tasks, &columns),
+        "## No project\n\n\
+        - [x] ~~Call \"Mom\"~~ — ID: 4, Priority: H\n\n\
+        ## Home\n\n\
+        - [ ] Paint the fence, white *(started)* — ID: 2\n\
+        - [x] Water the plants — ID: 3"
+    );
+
+    // Urgency first, then the lowest id:
+    let sort: Vec<SortKey> = ["urgency-", "id"]
+        .into_iter()
+        .map(|key| SortKey::try_from(key.to_string()).unwrap())
+        .collect();
+    assert_eq!(String::from(sort[1]), "id+");
+    assert!(SortKey::try_from("size-".to_string()).is_err());
+    let mut sorted = tasks.clone();
+    sort_tasks(&mut sorted, &tasks, &sort);
+    let ids: Vec<i64> = sorted.iter().map(|task| task.id).collect();
+    assert_eq!(ids, vec![2, 1, 3, 4]);
+}
@@ -0,0 +1,144 @@
+// search.rs
+
+use rusqlite::Connection;
+use std::collections::HashSet;
+use tabled::{builder::Builder, settings::Style};
+
+use crate::error::CrudError;
+use crate::task::TaskState;
+use crate::theme::THEME;
+
+// Full-text search ________________________________________________________________________________
+//
+// The tasks_fts table (see the migrations in database.rs) holds the title, notes and annotations of
+// every task and is kept up to date by triggers. Results are ranked with BM25, title hits count
+// most, then notes.
+const RANK: &str = "bm25(tasks_fts, 0.0, 10.0, 3.0, 1.0)";
+
+pub fn read_search(conn: &Connection, text: &str) -> Result<String, CrudError> {
+    let query = fts_query(text)?;
+    let (highlight_start, highlight_end) = THEME.highlight_marks();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT tasks.state, tasks.id, highlight(tasks_fts, 1, ?2, ?3),
+            snippet(tasks_fts, 2, ?2, ?3, '…', 8), snippet(tasks_fts, 3, ?2, ?3, '…', 8)
+        FROM tasks_fts JOIN tasks ON tasks.uuid = tasks_fts.uuid
+        WHERE tasks_fts MATCH ?1 AND tasks.deleted IS NULL
+        ORDER BY {}",
+        RANK
+    ))?;
+    let rows = stmt.query_map((&query, &highlight_start, &highlight_end), |row| {
+        Ok((
+            TaskState::to_state(row.get(0)?),
+            row.get::<_, Option<i64>>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
+
+    let mut builder = Builder::default();
+    builder.set_header([
+        "📝".to_string(),
+        THEME.header("Task"),
+        THEME.header("ID"),
+        THEME.header("Match"),
+    ]);
+    for row in rows {
+        let (state, id, title, notes, annotations) = row?;
+        // Only show notes and annotations that contributed to the match:
+        let snippets: Vec<String> = [notes, annotations]
+            .into_iter()
+            .flatten()
+            .filter(|snippet| snippet.contains(&highlight_start))
+            .collect();
+        builder.push_record([
+            state.get_icon().to_string(),
+            title,
+            // Archived tasks have no id:
+            id.map(|id| id.to_string()).unwrap_or_default(),
+            snippets.join(" … ").replace('\n', " "),
+        ]);
+    }
+    if builder.count_rows() == 0 {
+        return Err(CrudError::TaskNotFound(format!(
+            "No task matches '{}'",
+            text
+        )));
+    }
+    Ok(builder.build().with(Style::rounded()).to_string())
+}
+
+// Uuids of all tasks that match, for "text:~..." in filters.
+pub fn search_uuids(conn: &Connection, text: &str) -> Result<HashSet<String>, CrudError> {
+    let query = fts_query(text)?;
+    let mut stmt = conn.prepare("SELECT uuid FROM tasks_fts WHERE tasks_fts MATCH ?")?;
+    let uuids = stmt.query_map([query], |row| row.get(0))?;
+    Ok(uuids.collect::<Result<_, _>>()?)
+}
+
+// Helper functions --------------------------------------------------------------------------------
+
+// Every word has to appear, as a word or the start of one. Words are quoted, so FTS5 operators
+// like AND, NEAR or '*' in the input are searched for instead of interpreted.
+fn fts_query(text: &str) -> Result<String, CrudError> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    if words.is_empty() {
+        return Err(CrudError::TaskNotFound(
+            "Please enter something to search for".to_string(),
+        ));
+    }
+    Ok(words.join(" "))
+}
+
+#[cfg(test)]
+#[test]
+fn test_search() {
+    use crate::crud::{add_annotation, create_task, get_task_by_title};
+    use crate::task::Annotation;
+
+    crate::config::use_test_config();
+    let conn = Connection::open_in_memory().expect("Failed to create in-memory database");
+    crate::database::create_table(&conn).expect("Failed to create tables");
+    create_task(&conn, "Write the quarterly report".to_string(), &[]).unwrap();
+    create_task(&conn, "Call the bank".to_string(), &[]).unwrap();
+    let bank = get_task_by_title(&conn, "Call the bank").unwrap().unwrap();
+    let annotation = Annotation {
+        entry: chrono::Utc::now().to_string(),
+        description: "Ask about the annual report".to_string(),
+    };
+    add_annotation(&conn, &bank.uuid, &annotation).unwrap();
+
+    // Title hits rank before annotation hits:
+    let table = read_search(&conn, "report").unwrap();
+    let quarterly = table.find("quarterly").unwrap();
+    assert!(quarterly < table.find("Call the bank").unwrap());
+    assert!(table.contains("\x1b[1;33mreport\x1b[0m"));
+    assert!(read_search(&conn, "quarterly report").is_ok());
+    // FTS5 syntax in the input is searched for, not interpreted:
+    assert!(read_search(&conn, "NEAR(\"").is_err());
+
+    assert_eq!(
+        search_uuids(&conn, "annu").unwrap(),
+        HashSet::from([bank.uuid.clone()])
+    );
+    let filter = crate::filter::Filter::parse("not text:~\"annual report\"").unwrap();
+    let tasks = crate::crud::get_tasks(&conn, None).unwrap();
+    let found = filter.search(&conn).unwrap().apply(&tasks, &tasks);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].title, "Write the quarterly report");
+
+    conn.execute(
+        "UPDATE tasks SET title = 'Call Mom' WHERE uuid = ?",
+        [&bank.uuid],
+    )
+    .unwrap();
+    assert_eq!(search_uuids(&conn, "bank").unwrap().len(), 0);
+    conn.execute("UPDATE tasks SET notes = 'Bring the IBAN' WHERE uuid = ?", [&bank.uuid])
+        .unwrap();
+    assert!(read_search(&conn, "iban").unwrap().contains("\x1b[1;33mIBAN\x1b[0m"));
+    conn.execute("DELETE FROM annotations", []).unwrap();
+    assert_eq!(search_uuids(&conn, "annual").unwrap().len(), 0);
+}
@@ -0,0 +1,270 @@
+// shell.rs
+
+use rusqlite::Connection;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config as EditorConfig, Context, Editor, Helper};
+
+use crate::alias::{expand_aliases, split_words};
+use crate::color;
+use crate::completion::complete;
+use crate::config::CONFIG;
+use crate::database::before_command;
+use crate::error::CliError;
+use crate::lexer::{commands_help, run_command};
+use crate::parser::{is_command_name, parse, Command, COMMANDS};
+use crate::paths::data_dir;
+use crate::workspace::open_workspace;
+
+// Interactive shell _______________________________________________________________________________
+//
+// `pct shell` keeps one workspace open and reads command lines with the same grammar as `pct`,
+// without the "pct" in front. Lines can be edited, <TAB> completes like the shell completions and
+// the history is kept in the data folder. `use <workspace>` switches to another workspace.
+const SHELL_COMMANDS: [(&str, &str, &str); 4] = [
+    ("use", "<workspace>", "Switch to another workspace"),
+    ("help", "[command]", "Show all commands or how to use one"),
+    ("clear", "", "Clear the screen"),
+    ("exit", "", "Leave the shell, as does Ctrl-D"),
+];
+
+pub fn run_shell(workspace: &str) -> Result<String, CliError> {
+    let config = EditorConfig::builder()
+        .auto_add_history(true)
+        .history_ignore_dups(true)?
+        .history_ignore_space(true)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::with_config(config)?;
+    editor.set_helper(Some(ShellHelper {
+        conn: open_workspace(workspace)?,
+        workspace: workspace.to_string(),
+    }));
+    let history_file = data_dir()?.join("shell_history");
+    // There is no history before the first session:
+    let _ = editor.load_history(&history_file);
+    println!("Pocato shell, type 'help' for all commands and 'exit' to leave.");
+
+    loop {
+        let prompt = format!("pct ({})> ", helper(&editor).workspace);
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            // Ctrl-C only drops the line:
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                // Keep what was typed so far, the error is what counts though:
+                let _ = editor.save_history(&history_file);
+                return Err(err.into());
+            }
+        };
+        let mut words = split_words(&line);
+        // Lines copied from a terminal may start with "pct":
+        if words.first().is_some_and(|word| word == "pct") {
+            words.remove(0);
+        }
+        let result = match words.first().map(String::as_str) {
+            None => continue,
+            Some("exit" | "quit") => break,
+            Some("clear") => editor
+                .clear_screen()
+                .map(|_| String::new())
+                .map_err(Into::into),
+            Some("help" | "?") => Ok(shell_help(words.get(1))),
+            Some("use") => match (words.get(1), words.get(2)) {
+                (Some(name), None) => open_workspace(name)
+                    .map(|conn| {
+                        let helper = editor.helper_mut().expect("The shell has a helper");
+                        (helper.conn, helper.workspace) = (conn, name.clone());
+                        format!("Using workspace \x1b[1;34m{}\x1b[0m", name)
+                    })
+                    .map_err(Into::into),
+                _ => Err(CliError::InvalidCommandArguments(
+                    "Which workspace? e.g. use work".to_string(),
+                )),
+            },
+            Some(_) => run_line(&helper(&editor).conn, &words),
+        };
+        match result {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", color::stdout(output)),
+            Err(err) => eprintln!("{}", color::stderr(err)),
+        }
+    }
+
+    editor.save_history(&history_file)?;
+    Ok(String::new())
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn helper(editor: &Editor<ShellHelper, DefaultHistory>) -> &ShellHelper {
+    editor.helper().expect("The shell has a helper")
+}
+
+// Aliases and macros work like on the command line, a macro stops at its first error.
+fn run_line(conn: &Connection, words: &[String]) -> Result<String, CliError> {
+    let mut outputs = Vec::new();
+    for command_line in expand_aliases(words)? {
+        let command_line = parse(&command_line)?;
+        match command_line.command {
+            Command::Help => outputs.push(shell_help(None)),
+            _ => {
+                before_command(conn, &command_line.command);
+                outputs.push(run_command(conn, command_line)?)
+            }
+        }
+    }
+    Ok(outputs.join("\n"))
+}
+
+// All commands, or the usage of one of them, an alias or a report.
+fn shell_help(command: Option<&String>) -> String {
+    let Some(name) = command else {
+        let shell_commands: Vec<String> = SHELL_COMMANDS
+            .iter()
+            .map(|(name, args, about)| format!("  {:36}  {}", format!("{} {}", name, args), about))
+            .collect();
+        return format!(
+            "Shell:\n{}\n\n{}",
+            shell_commands.join("\n"),
+            commands_help()
+        );
+    };
+    let mut commands = SHELL_COMMANDS.iter().chain(COMMANDS.iter());
+    if let Some((name, args, about)) = commands.find(|(command, _, _)| command == name) {
+        return format!("{} {}\n  {}", name, args, about);
+    }
+    if let Some(alias) = CONFIG.alias.get(name) {
+        return format!("{}: alias for {}", name, alias.command_lines().join("; "));
+    }
+    match CONFIG.report.get(name) {
+        Some(report) => format!(
+            "{}: report of {}",
+            name,
+            report.filter.as_deref().unwrap_or("all tasks")
+        ),
+        None => format!(
+            "There is no command '{}', type 'help' for all of them",
+            name
+        ),
+    }
+}
+
+// Line editor helper ______________________________________________________________________________
+//
+// Holds the open workspace, so <TAB> completes ids, tags and projects without reopening it.
+struct ShellHelper {
+    conn: Connection,
+    workspace: String,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        // The word as typed may have quotes, which split_words takes off:
+        let (start, quote) = word_start(line);
+        let mut words = split_words(line);
+        if start == line.len() {
+            words.push(String::new());
+        }
+        let current = words.last().cloned().unwrap_or_default();
+        // `use` takes a workspace like `--workspace` does:
+        let (words, shell_commands) = match words.as_slice() {
+            [_] => (
+                words.clone(),
+                SHELL_COMMANDS
+                    .map(|(name, _, about)| (name, about))
+                    .to_vec(),
+            ),
+            [first, _] if first == "use" => (vec!["--workspace".to_string(), current], vec![]),
+            _ => (words.clone(), vec![]),
+        };
+        let mut candidates: Vec<Pair> = shell_commands
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(words[0].as_str()) && !is_command_name(name))
+            .map(|(name, about)| candidate(name, about, quote))
+            .collect();
+        // Completion is a convenience, errors just mean there is nothing to offer:
+        let completions = complete(&self.conn, &words).unwrap_or_default();
+        for completion in completions.lines() {
+            let (value, about) = completion.split_once('\t').unwrap_or((completion, ""));
+            candidates.push(candidate(value, about, quote));
+        }
+        Ok((start, candidates))
+    }
+}
+
+// Values with spaces are quoted, in the quote the word was started with.
+fn candidate(value: &str, about: &str, quote: Option<char>) -> Pair {
+    let replacement = match (quote, value.contains(char::is_whitespace)) {
+        (Some(quote), _) => format!("{}{}{}", quote, value, quote),
+        (None, true) => format!("'{}'", value),
+        (None, false) => value.to_string(),
+    };
+    Pair {
+        display: format!("{:16} {}", value, about),
+        replacement,
+    }
+}
+
+// Byte position where the last word of the line starts, after the last whitespace outside of
+// quotes, and the quote it opens with.
+fn word_start(line: &str) -> (usize, Option<char>) {
+    let (mut start, mut quote) = (0, None);
+    for (index, char) in line.char_indices() {
+        match (quote, char) {
+            (None, '"' | '\'') => quote = Some(char),
+            (Some(open), _) if char == open => quote = None,
+            (None, _) if char.is_whitespace() => start = index + char.len_utf8(),
+            _ => {}
+        }
+    }
+    let opening = line[start..].chars().next().filter(|char| ['"', '\''].contains(char));
+    (start, opening)
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+#[cfg(test)]
+#[test]
+fn test_shell() {
+    crate::config::use_test_config();
+    let conn = Connection::open_in_memory().unwrap();
+    crate::database::create_table(&conn).unwrap();
+    let run = |line: &str| run_line(&conn, &split_words(line));
+
+    let added = run("add 'Clean room' +home").unwrap();
+    assert!(added.contains("Clean room") && added.contains("(#1)"));
+    assert!(run("f 1").unwrap().starts_with("Finished:"));
+    assert!(run("shell").is_err());
+    assert_eq!(shell_help(Some(&"f".to_string())), "f: alias for finish");
+    assert_eq!(
+        shell_help(Some(&"use".to_string())),
+        "use <workspace>\n  Switch to another workspace"
+    );
+    assert!(shell_help(None).contains("Commands:\n  add <title>"));
+
+    assert_eq!(word_start("add 'Cle"), (4, Some('\'')));
+    assert_eq!(word_start("add \"Clean ro"), (4, Some('"')));
+    assert_eq!(word_start("3 fin"), (2, None));
+    assert_eq!(candidate("Clean room", "", Some('"')).replacement, "\"Clean room\"");
+}
@@ -1,26 +1,33 @@
 // task.rs
 
-use crate::config;
+use crate::theme;
 use chrono::prelude::*;
 use colored::*;
 use core::fmt;
-use tabled::Tabled;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 // Define and implement Task -----------------------------------------------------------------------
-#[derive(Debug, Clone, Tabled)]
+#[derive(Debug, Clone)]
 pub struct Task {
-    #[tabled(rename = "📝")]
     pub state: TaskState,
-    #[tabled(rename = "\x1b[1;34mTask\x1b[0m")]
     pub title: String,
-    #[tabled(rename = "\x1b[1;34mID\x1b[0m")]
     pub id: i64,
     pub uuid: String,
     pub created: String,
     pub project: bool,
     pub parent: String,
-    // tags: Vec<String>,
+    pub tags: Vec<String>,
+    pub due: Option<String>,
+    pub priority: Option<String>,
+    pub depends: Vec<String>,
+    pub ended: Option<String>,
+    /// Set while the task is in the trash
+    pub deleted: Option<String>,
+    /// Set once a finished or cancelled task is archived
+    pub archived: Option<String>,
+    /// Long-form text, edited with $EDITOR
+    pub notes: Option<String>,
 }
 
 impl Task {
@@ -33,8 +40,66 @@ impl Task {
             created: Utc::now().to_string(),
             project: false,
             parent: "".to_string(),
+            tags: Vec::new(),
+            due: None,
+            priority: None,
+            depends: Vec::new(),
+            ended: None,
+            deleted: None,
+            archived: None,
+            notes: None,
         }
     }
+
+    pub fn created_utc(&self) -> Option<DateTime<Utc>> {
+        self.created.parse().ok()
+    }
+
+    pub fn due_utc(&self) -> Option<DateTime<Utc>> {
+        self.due.as_ref().and_then(|due| due.parse().ok())
+    }
+
+    pub fn ended_utc(&self) -> Option<DateTime<Utc>> {
+        self.ended.as_ref().and_then(|ended| ended.parse().ok())
+    }
+
+    pub fn deleted_utc(&self) -> Option<DateTime<Utc>> {
+        self.deleted.as_ref().and_then(|deleted| deleted.parse().ok())
+    }
+
+    pub fn archived_utc(&self) -> Option<DateTime<Utc>> {
+        self.archived.as_ref().and_then(|archived| archived.parse().ok())
+    }
+
+    // Names of all project tasks above this task, e.g. "Home.Garden".
+    pub fn project_path(&self, by_uuid: &HashMap<&str, &Task>) -> Option<String> {
+        let mut projects = Vec::new();
+        let mut parent = by_uuid.get(self.parent.as_str());
+        // The depth limit guards against parent cycles:
+        while let (Some(task), true) = (parent, projects.len() < 100) {
+            if task.project {
+                projects.push(task.title.clone());
+            }
+            parent = by_uuid.get(task.parent.as_str());
+        }
+        projects.reverse();
+        (!projects.is_empty()).then(|| projects.join("."))
+    }
+}
+
+// Date-only values (due dates, todo.txt dates ...) mean the start of that day in local time.
+pub fn start_of_day(date: NaiveDate) -> Option<DateTime<Utc>> {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+        .earliest()
+        .map(|start| start.with_timezone(&Utc))
+}
+
+// Timestamped one-line comment attached to a task -------------------------------------------------
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub entry: String,
+    pub description: String,
 }
 
 // Define and implement Task states ----------------------------------------------------------------
@@ -51,11 +116,12 @@ pub enum TaskState {
 
 impl fmt::Display for TaskState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", TaskState::get_icon(&self))
+        write!(f, "{}", TaskState::get_icon(self))
     }
 }
 
 impl TaskState {
+    #[allow(clippy::inherent_to_string_shadow_display)]
     pub fn to_string(&self) -> String {
         match self {
             TaskState::Pending => "Pending".to_string(),
@@ -68,40 +134,30 @@ impl TaskState {
         }
     }
 
+    // Finished and cancelled tasks are closed, they get an end date.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TaskState::Finished | TaskState::Cancelled)
+    }
+
     pub fn to_state(text: String) -> TaskState {
-        match text.as_str() {
-            "Pending" => return TaskState::Pending,
-            "Started" => TaskState::Started,
-            "Finished" => TaskState::Finished,
-            "Blocked" => TaskState::Blocked,
-            "Someday" => TaskState::Someday,
-            "Cancelled" => TaskState::Cancelled,
-            "Paused" => TaskState::Paused,
-            _ => unreachable!("Task state does not exist"),
+        TaskState::from_name(&text).expect("Task state does not exist")
+    }
+
+    // Case insensitive, so "started" works as well as "Started":
+    pub fn from_name(text: &str) -> Option<TaskState> {
+        match text.to_lowercase().as_str() {
+            "pending" => Some(TaskState::Pending),
+            "started" => Some(TaskState::Started),
+            "finished" => Some(TaskState::Finished),
+            "blocked" => Some(TaskState::Blocked),
+            "someday" => Some(TaskState::Someday),
+            "cancelled" => Some(TaskState::Cancelled),
+            "paused" => Some(TaskState::Paused),
+            _ => None,
         }
     }
+    // Icon and color come from the theme in config.toml
     pub fn get_icon(&self) -> ColoredString {
-        if config::CONFIG.nerd_font {
-            match self {
-                TaskState::Pending => "".white(),
-                TaskState::Started => "".yellow(),
-                TaskState::Finished => "".green(),
-                TaskState::Blocked => "".blue(),
-                TaskState::Someday => "".yellow(),
-                TaskState::Cancelled => "".red(),
-                TaskState::Paused => "".white(),
-            }
-        } else {
-            // Alternative icons when Nerdfonts are not available:
-            match self {
-                TaskState::Pending => "[ ]".white(),
-                TaskState::Started => "[|]".yellow(),
-                TaskState::Finished => "[√]".green(),
-                TaskState::Blocked => "[#]".blue(),
-                TaskState::Someday => "[~]".yellow(),
-                TaskState::Cancelled => "[x]".red(),
-                TaskState::Paused => "[-]".white(),
-            }
-        }
+        theme::THEME.icon(self)
     }
 }
@@ -0,0 +1,285 @@
+// taskwarrior.rs
+
+use chrono::prelude::*;
+use serde_json::{json, Map, Value};
+
+use crate::error::ExchangeError;
+use crate::exchange::{parse_date_time, Record, DATE_TIME_FORMAT};
+use crate::task::{Annotation, Task, TaskState};
+
+// Taskwarrior JSON (`task export` / `task import`) ________________________________________________
+
+// Fields Taskwarrior computes itself, they are neither imported nor kept as annotations.
+const DERIVED_FIELDS: [&str; 3] = ["id", "urgency", "modified"];
+
+pub fn to_json(records: &[Record]) -> String {
+    let tasks: Vec<String> = records
+        .iter()
+        .map(|record| to_object(record).to_string())
+        .collect();
+    format!("[\n{}\n]", tasks.join(",\n"))
+}
+
+pub fn from_json(json: &str) -> Result<Vec<Record>, ExchangeError> {
+    let invalid =
+        |error: serde_json::Error| ExchangeError::InvalidFormat("Taskwarrior", error.to_string());
+
+    // `task export` writes a JSON array, older versions one JSON object per line:
+    let objects = match json.trim_start().starts_with('[') {
+        true => match serde_json::from_str::<Value>(json).map_err(invalid)? {
+            Value::Array(objects) => objects,
+            _ => unreachable!("JSON starting with '[' is an array"),
+        },
+        false => json
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(invalid))
+            .collect::<Result<_, _>>()?,
+    };
+
+    objects
+        .into_iter()
+        .enumerate()
+        .map(|(index, object)| match object {
+            Value::Object(object) => from_object(object, index + 1),
+            _ => Err(ExchangeError::InvalidFormat(
+                "Taskwarrior",
+                format!("Task {} is not a JSON object", index + 1),
+            )),
+        })
+        .collect()
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn to_object(record: &Record) -> Value {
+    let task = &record.task;
+    let mut object = Map::new();
+    object.insert("uuid".to_string(), json!(task.uuid));
+    object.insert("description".to_string(), json!(task.title));
+    if let Some(created) = task.created_utc() {
+        object.insert("entry".to_string(), json!(format_date_time(created)));
+    }
+
+    let status = match task.state {
+        TaskState::Finished => "completed",
+        TaskState::Cancelled => "deleted",
+        _ => "pending",
+    };
+    object.insert("status".to_string(), json!(status));
+    if let TaskState::Started = task.state {
+        // Pocato doesn't know when a task was started:
+        let start = task.created_utc().unwrap_or_else(Utc::now);
+        object.insert("start".to_string(), json!(format_date_time(start)));
+    }
+    if let Some(ended) = task.ended_utc() {
+        object.insert("end".to_string(), json!(format_date_time(ended)));
+    }
+    // States Taskwarrior doesn't have are kept in a user defined attribute:
+    if let TaskState::Blocked | TaskState::Someday | TaskState::Paused = task.state {
+        object.insert("pocato_state".to_string(), json!(task.state.to_string()));
+    }
+
+    if let Some(project) = &record.project {
+        object.insert("project".to_string(), json!(project));
+    }
+    if !task.parent.is_empty() && record.project.is_none() {
+        object.insert("pocato_parent".to_string(), json!(task.parent));
+    }
+    if !task.tags.is_empty() {
+        object.insert("tags".to_string(), json!(task.tags));
+    }
+    if let Some(due) = task.due_utc() {
+        object.insert("due".to_string(), json!(format_date_time(due)));
+    }
+    if let Some(priority) = &task.priority {
+        object.insert("priority".to_string(), json!(priority));
+    }
+    if !task.depends.is_empty() {
+        object.insert("depends".to_string(), json!(task.depends));
+    }
+    if !record.annotations.is_empty() {
+        let annotations: Vec<Value> = record
+            .annotations
+            .iter()
+            .map(|annotation| {
+                let entry = annotation.entry.parse().unwrap_or_else(|_| Utc::now());
+                json!({
+                    "entry": format_date_time(entry),
+                    "description": annotation.description,
+                })
+            })
+            .collect();
+        object.insert("annotations".to_string(), Value::Array(annotations));
+    }
+    Value::Object(object)
+}
+
+fn from_object(mut object: Map<String, Value>, number: usize) -> Result<Record, ExchangeError> {
+    let mut take_string = |key: &str| match object.remove(key) {
+        Some(Value::String(value)) => Some(value),
+        _ => None,
+    };
+    let (uuid, title) = match (take_string("uuid"), take_string("description")) {
+        (Some(uuid), Some(title)) if !title.trim().is_empty() => (uuid, title),
+        _ => {
+            return Err(ExchangeError::InvalidFormat(
+                "Taskwarrior",
+                format!("Task {} needs a uuid and a description", number),
+            ))
+        }
+    };
+    let status = take_string("status");
+    let pocato_state = take_string("pocato_state");
+    let entry = take_string("entry");
+    let end = take_string("end");
+    let due = take_string("due");
+    let project = take_string("project").filter(|project| !project.is_empty());
+    let parent = take_string("pocato_parent");
+    let (priority, unknown_priority) = match take_string("priority") {
+        Some(priority) if ["H", "M", "L"].contains(&priority.as_str()) => (Some(priority), None),
+        priority => (None, priority),
+    };
+
+    // Only a pending task can be started, for all others "start" is kept as an annotation:
+    let start = match status.as_deref() {
+        Some("pending") | None => take_string("start"),
+        _ => None,
+    };
+    let state = match pocato_state.as_deref().and_then(TaskState::from_name) {
+        Some(state) => state,
+        None => match (status.as_deref(), start) {
+            (Some("completed"), _) => TaskState::Finished,
+            (Some("deleted"), _) => TaskState::Cancelled,
+            (Some("waiting"), _) => TaskState::Someday,
+            (_, Some(_)) => TaskState::Started,
+            _ => TaskState::Pending,
+        },
+    };
+
+    let mut task = Task::new(title, 0);
+    task.uuid = uuid;
+    task.state = state;
+    if let Some(created) = entry.as_deref().and_then(parse_date_time) {
+        task.created = created.to_string();
+    }
+    if task.state.is_closed() {
+        let ended = end
+            .as_deref()
+            .and_then(parse_date_time)
+            .unwrap_or_else(Utc::now);
+        task.ended = Some(ended.to_string());
+    }
+    task.due = due
+        .as_deref()
+        .and_then(parse_date_time)
+        .map(|due| due.to_string());
+    task.priority = priority;
+    task.parent = parent.unwrap_or_default();
+    task.tags = string_list(object.remove("tags"));
+    task.depends = string_list(object.remove("depends"));
+
+    let mut annotations = match object.remove("annotations") {
+        Some(Value::Array(annotations)) => annotations
+            .into_iter()
+            .filter_map(|annotation| {
+                let description = annotation.get("description")?.as_str()?.to_string();
+                let entry = annotation
+                    .get("entry")
+                    .and_then(Value::as_str)
+                    .and_then(parse_date_time)
+                    .unwrap_or_else(Utc::now);
+                Some(Annotation {
+                    entry: entry.to_string(),
+                    description,
+                })
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    // Everything that pocato has no field for is kept as an annotation:
+    if let Some(priority) = unknown_priority {
+        object.insert("priority".to_string(), Value::String(priority));
+    }
+    for (key, value) in object {
+        if DERIVED_FIELDS.contains(&key.as_str()) {
+            continue;
+        }
+        let value = match value {
+            Value::String(value) => value,
+            value => value.to_string(),
+        };
+        annotations.push(Annotation {
+            entry: Utc::now().to_string(),
+            description: format!("{}: {}", key, value),
+        });
+    }
+
+    Ok(Record {
+        task,
+        project,
+        annotations,
+    })
+}
+
+fn format_date_time(date_time: DateTime<Utc>) -> String {
+    date_time.format(DATE_TIME_FORMAT).to_string()
+}
+
+// Lists are JSON arrays, except for "depends" which older Taskwarrior versions join with commas.
+fn string_list(value: Option<Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(items)) => items
+            .into_iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        Some(Value::String(items)) => items
+            .split(',')
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_taskwarrior_import() {
+    let json = r#"[
+{"id":1,"description":"Plant tomatoes","entry":"20231019T120000Z","modified":"20231020T080000Z",
+"status":"pending","start":"20231020T080000Z","uuid":"5ea4ad4f-5d0c-4d63-bd6a-4c1dc9b1bc39",
+"project":"Home.Garden","tags":["outside","spring"],"due":"20231101T000000Z","priority":"H",
+"depends":"c1,c2","recur":"weekly","urgency":12.3,
+"annotations":[{"entry":"20231019T130000Z","description":"Buy soil first"}]},
+{"id":0,"description":"Old chore","entry":"20231001T120000Z","end":"20231002T120000Z",
+"status":"completed","uuid":"c1","priority":"urgent"}
+]"#;
+    let records = from_json(json).expect("Failed to parse Taskwarrior export");
+    assert_eq!(records.len(), 2);
+
+    let record = &records[0];
+    assert_eq!(record.task.state.to_string(), "Started");
+    assert_eq!(record.task.tags, vec!["outside", "spring"]);
+    assert_eq!(record.task.depends, vec!["c1", "c2"]);
+    assert_eq!(record.task.priority.as_deref(), Some("H"));
+    assert_eq!(record.project.as_deref(), Some("Home.Garden"));
+    assert!(record.task.due_utc().is_some());
+    let descriptions: Vec<&str> = record
+        .annotations
+        .iter()
+        .map(|a| a.description.as_str())
+        .collect();
+    assert_eq!(descriptions, vec!["Buy soil first", "recur: weekly"]);
+
+    let record = &records[1];
+    assert_eq!(record.task.state.to_string(), "Finished");
+    assert_eq!(record.task.ended_utc().map(|ended| ended.day()), Some(2));
+    assert_eq!(record.task.priority, None);
+    assert_eq!(record.annotations[0].description, "priority: urgent");
+
+    // And back again:
+    let exported = from_json(&to_json(&records)).expect("Failed to parse own export");
+    assert_eq!(exported[0].task.state.to_string(), "Started");
+    assert_eq!(exported[0].project.as_deref(), Some("Home.Garden"));
+    assert_eq!(exported[0].annotations.len(), 2);
+}
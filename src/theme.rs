@@ -0,0 +1,257 @@
+// theme.rs
+
+use colored::{Color, ColoredString, Colorize};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::color;
+use crate::config::{Config, ThemeConfig, CONFIG};
+use crate::error::ConfigError;
+use crate::task::TaskState;
+
+pub static THEME: Lazy<Theme> = Lazy::new(|| match Theme::from_config(&CONFIG) {
+    Ok(theme) => theme,
+    Err(error) => {
+        eprintln!("{}", color::stderr(error));
+        Theme::built_in("dark", CONFIG.nerd_font).expect("The dark theme is built in")
+    }
+});
+
+// Themes __________________________________________________________________________________________
+//
+// A theme sets the icon and color of every task state, the color of table headers and of search
+// matches. Built-in
+// themes use Nerd Font icons when nerd_font is set, user themes in [themes.<name>] start from one
+// of them and change what they list.
+pub const BUILT_IN_THEMES: [&str; 4] = ["dark", "light", "solarized", "monochrome"];
+
+const STATES: [&str; 7] = [
+    "pending",
+    "started",
+    "finished",
+    "blocked",
+    "someday",
+    "cancelled",
+    "paused",
+];
+const NERD_ICONS: [&str; 7] = [
+    "\u{f096}", "\u{f044}", "\u{f046}", "\u{f256}", "\u{f006}", "\u{f014}", "\u{f520}",
+];
+const ASCII_ICONS: [&str; 7] = ["[ ]", "[|]", "[√]", "[#]", "[~]", "[x]", "[-]"];
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// None leaves headers uncolored, they are bold either way
+    header: Option<Color>,
+    /// Search matches, bold as well
+    highlight: Option<Color>,
+    states: HashMap<&'static str, StateStyle>,
+}
+
+#[derive(Debug, Clone)]
+struct StateStyle {
+    icon: String,
+    color: Option<Color>,
+}
+
+impl Theme {
+    pub fn from_config(config: &Config) -> Result<Self, ConfigError> {
+        let name = config.theme.as_deref().unwrap_or("dark");
+        if let Some(theme) = Theme::built_in(name, config.nerd_font) {
+            return Ok(theme);
+        }
+        match config.themes.get(name) {
+            Some(theme_config) => Theme::from_theme_config(name, theme_config, config.nerd_font),
+            None => Err(ConfigError::InvalidTheme(format!(
+                "There is no theme '{}', choose one of {} or add [themes.{}].",
+                name,
+                BUILT_IN_THEMES.join(", "),
+                name
+            ))),
+        }
+    }
+
+    pub fn built_in(name: &str, nerd_font: bool) -> Option<Self> {
+        use Color::*;
+        let (header, highlight, colors) = match name {
+            "dark" => (
+                Some(Blue),
+                Some(Yellow),
+                [White, Yellow, Green, Blue, Yellow, Red, White].map(Some),
+            ),
+            "light" => (
+                Some(Blue),
+                Some(Magenta),
+                [Black, Magenta, Green, Blue, Cyan, Red, BrightBlack].map(Some),
+            ),
+            "solarized" => (
+                Some(rgb(0x268bd2)),
+                Some(rgb(0xb58900)),
+                [
+                    0x839496, 0xb58900, 0x859900, 0x268bd2, 0x6c71c4, 0xdc322f, 0x586e75,
+                ]
+                .map(|color| Some(rgb(color))),
+            ),
+            "monochrome" => (None, None, [None; 7]),
+            _ => return None,
+        };
+        let icons = if nerd_font { NERD_ICONS } else { ASCII_ICONS };
+        let states = STATES
+            .iter()
+            .zip(icons.iter().zip(colors))
+            .map(|(state, (icon, color))| {
+                let icon = icon.to_string();
+                (*state, StateStyle { icon, color })
+            })
+            .collect();
+        Some(Theme {
+            header,
+            highlight,
+            states,
+        })
+    }
+
+    pub fn icon(&self, state: &TaskState) -> ColoredString {
+        let style = &self.states[state_key(state)];
+        match style.color {
+            Some(color) => style.icon.as_str().color(color),
+            None => style.icon.as_str().normal(),
+        }
+    }
+
+    pub fn header(&self, text: &str) -> String {
+        match self.header {
+            Some(color) => text.color(color).bold().to_string(),
+            None => text.bold().to_string(),
+        }
+    }
+
+    // The escape codes around search matches, SQLite puts them into the text.
+    pub fn highlight_marks(&self) -> (String, String) {
+        let start = match &self.highlight {
+            Some(color) => format!("\x1b[1;{}m", color.to_fg_str()),
+            None => "\x1b[1m".to_string(),
+        };
+        (start, "\x1b[0m".to_string())
+    }
+
+    fn from_theme_config(
+        name: &str,
+        theme_config: &ThemeConfig,
+        nerd_font: bool,
+    ) -> Result<Self, ConfigError> {
+        let base = theme_config.base.as_deref().unwrap_or("dark");
+        let mut theme = Theme::built_in(base, nerd_font).ok_or_else(|| {
+            ConfigError::InvalidTheme(format!(
+                "Theme '{}' is based on '{}', which isn't one of {}.",
+                name,
+                base,
+                BUILT_IN_THEMES.join(", ")
+            ))
+        })?;
+        if let Some(header) = &theme_config.header {
+            theme.header = parse_color(header)?;
+        }
+        if let Some(highlight) = &theme_config.highlight {
+            theme.highlight = parse_color(highlight)?;
+        }
+        for (state, style_config) in &theme_config.states {
+            let key = TaskState::from_name(state).map(|state| state_key(&state));
+            let style = match key.and_then(|key| theme.states.get_mut(key)) {
+                Some(style) => style,
+                None => {
+                    return Err(ConfigError::InvalidTheme(format!(
+                        "Theme '{}' styles '{}', which isn't a task state.",
+                        name, state
+                    )))
+                }
+            };
+            if let Some(icon) = &style_config.icon {
+                style.icon = icon.clone();
+            }
+            if let Some(color) = &style_config.color {
+                style.color = parse_color(color)?;
+            }
+        }
+        Ok(theme)
+    }
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn state_key(state: &TaskState) -> &'static str {
+    match state {
+        TaskState::Pending => "pending",
+        TaskState::Started => "started",
+        TaskState::Finished => "finished",
+        TaskState::Blocked => "blocked",
+        TaskState::Someday => "someday",
+        TaskState::Cancelled => "cancelled",
+        TaskState::Paused => "paused",
+    }
+}
+
+fn rgb(hex: u32) -> Color {
+    Color::TrueColor {
+        r: (hex >> 16) as u8,
+        g: (hex >> 8) as u8,
+        b: hex as u8,
+    }
+}
+
+// Color names like "yellow" or "bright blue", "#rrggbb" or "none".
+fn parse_color(value: &str) -> Result<Option<Color>, ConfigError> {
+    let hex = value
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+    match (value, hex) {
+        ("none", _) => Ok(None),
+        (_, Some(hex)) => Ok(Some(rgb(hex))),
+        _ => match Color::from_str(value) {
+            Ok(color) => Ok(Some(color)),
+            Err(_) => Err(ConfigError::InvalidTheme(format!(
+                "Unknown color '{}', use a name like \"bright blue\" or \"#268bd2\".",
+                value
+            ))),
+        },
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_themes() {
+    crate::config::use_test_config();
+    let dark = Theme::built_in("dark", true).unwrap();
+    assert_eq!(
+        dark.icon(&TaskState::Started).to_string(),
+        "\x1b[33m\u{f044}\x1b[0m"
+    );
+    assert_eq!(dark.header("ID"), "\x1b[1;34mID\x1b[0m");
+    let monochrome = Theme::built_in("monochrome", false).unwrap();
+    assert_eq!(monochrome.icon(&TaskState::Finished).to_string(), "[√]");
+    assert_eq!(monochrome.highlight_marks().0, "\x1b[1m");
+
+    let mut config: Config = toml::from_str(
+        "nerd_font = false\n\
+        theme = \"mine\"\n\
+        [themes.mine]\n\
+        base = \"monochrome\"\n\
+        header = \"#268bd2\"\n\
+        highlight = \"green\"\n\
+        states.started = { icon = \">\", color = \"bright red\" }",
+    )
+    .unwrap();
+    let mine = Theme::from_config(&config).unwrap();
+    assert_eq!(
+        mine.icon(&TaskState::Started).to_string(),
+        "\x1b[91m>\x1b[0m"
+    );
+    assert_eq!(mine.icon(&TaskState::Pending).to_string(), "[ ]");
+    assert_eq!(mine.header("ID"), "\x1b[1;38;2;38;139;210mID\x1b[0m");
+    assert_eq!(mine.highlight_marks().0, "\x1b[1;32m");
+
+    config.theme = Some("missing".to_string());
+    assert!(Theme::from_config(&config).is_err());
+    assert!(parse_color("blurple").is_err());
+}
@@ -0,0 +1,221 @@
+// todotxt.rs
+
+use chrono::prelude::*;
+
+use crate::error::ExchangeError;
+use crate::exchange::Record;
+use crate::task::{start_of_day, Task, TaskState};
+
+// todo.txt format (https://github.com/todotxt/todo.txt) ___________________________________________
+//
+// x 2026-10-01 2026-09-20 Water the plants +Home @garden due:2026-10-01 uuid:5ea4ad4f-...
+//
+// Priorities (A), (B) and (C) are pocato's H, M and L. States other than Pending and Finished, the
+// uuid and non-project parents are stored as key:value extensions so they survive a round-trip.
+// Spaces in project names are written as underscores, and projects after the first become tags.
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+pub fn to_todo_txt(records: &[Record]) -> String {
+    records.iter().map(to_line).collect::<Vec<_>>().join("\n")
+}
+
+pub fn from_todo_txt(todo_txt: &str) -> Result<Vec<Record>, ExchangeError> {
+    todo_txt
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| from_line(line, index + 1))
+        .collect()
+}
+
+// Helper functions --------------------------------------------------------------------------------
+fn to_line(record: &Record) -> String {
+    let task = &record.task;
+    let priority = task.priority.as_deref().and_then(priority_to_letter);
+    let mut words = Vec::new();
+
+    if task.state.is_closed() {
+        words.push("x".to_string());
+        if let Some(ended) = task.ended_utc() {
+            words.push(ended.with_timezone(&Local).format(DATE_FORMAT).to_string());
+        }
+    } else if let Some(priority) = priority {
+        words.push(format!("({})", priority));
+    }
+    if let Some(created) = task.created_utc() {
+        words.push(created.with_timezone(&Local).format(DATE_FORMAT).to_string());
+    }
+
+    words.push(task.title.clone());
+    if let Some(project) = &record.project {
+        words.push(format!("+{}", project.replace(' ', "_")));
+    }
+    words.extend(task.tags.iter().map(|tag| format!("@{}", tag)));
+    if let Some(due) = task.due_utc() {
+        words.push(format!("due:{}", due.with_timezone(&Local).format(DATE_FORMAT)));
+    }
+    // Completed tasks lose their priority, todo.txt keeps it in a "pri" extension instead:
+    if let (true, Some(priority)) = (task.state.is_closed(), priority) {
+        words.push(format!("pri:{}", priority));
+    }
+    if !matches!(task.state, TaskState::Pending | TaskState::Finished) {
+        words.push(format!("state:{}", task.state.to_string().to_lowercase()));
+    }
+    if !task.parent.is_empty() && record.project.is_none() {
+        words.push(format!("parent:{}", task.parent));
+    }
+    words.push(format!("uuid:{}", task.uuid));
+    words.join(" ")
+}
+
+fn from_line(line: &str, number: usize) -> Result<Record, ExchangeError> {
+    let mut words = line.split_whitespace().peekable();
+
+    let done = words.next_if_eq(&"x").is_some();
+    let mut priority = match done {
+        true => None,
+        false => words.next_if(|word| is_priority(word)),
+    }
+    .and_then(|word| priority_from_letter(&word[1..2]));
+    // A completed task has the completion date first and then the (optional) creation date:
+    let first_date = words.next_if(|word| parse_date(word).is_some());
+    let second_date = words.next_if(|word| parse_date(word).is_some());
+    let (ended, created) = match done {
+        true => (first_date, second_date),
+        false => (None, first_date),
+    };
+
+    let mut task = Task::new(String::new(), 0);
+    let mut project = None;
+    let mut state = None;
+    let mut title = Vec::new();
+    for word in words {
+        match (word.split_once(':'), word.chars().next()) {
+            (_, Some('+')) if project.is_none() && word.len() > 1 => {
+                project = Some(word[1..].replace('_', " "))
+            }
+            (_, Some('+')) if word.len() > 1 => task.tags.push(word[1..].to_string()),
+            (_, Some('@')) if word.len() > 1 => task.tags.push(word[1..].to_string()),
+            (Some(("due", due)), _) if parse_date(due).is_some() => {
+                task.due = parse_date(due).map(|due| due.to_string())
+            }
+            (Some(("pri", letter)), _) if priority_from_letter(letter).is_some() => {
+                priority = priority_from_letter(letter)
+            }
+            (Some(("state", name)), _) if TaskState::from_name(name).is_some() => {
+                state = TaskState::from_name(name)
+            }
+            (Some(("uuid", uuid)), _) if !uuid.is_empty() => task.uuid = uuid.to_string(),
+            (Some(("parent", parent)), _) if !parent.is_empty() => task.parent = parent.to_string(),
+            // Everything else, including unknown extensions, stays part of the title:
+            _ => title.push(word),
+        }
+    }
+
+    if title.is_empty() {
+        return Err(ExchangeError::InvalidFormat(
+            "todo.txt",
+            format!("Line {} has no task description", number),
+        ));
+    }
+    task.title = title.join(" ");
+    task.priority = priority;
+    task.state = match (state, done) {
+        (Some(state), _) => state,
+        (None, true) => TaskState::Finished,
+        (None, false) => TaskState::Pending,
+    };
+    if let Some(created) = created.and_then(parse_date) {
+        task.created = created.to_string();
+    }
+    if task.state.is_closed() {
+        let ended = ended.and_then(parse_date).unwrap_or_else(Utc::now);
+        task.ended = Some(ended.to_string());
+    }
+
+    Ok(Record {
+        task,
+        project,
+        annotations: Vec::new(),
+    })
+}
+
+fn parse_date(word: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(word, DATE_FORMAT)
+        .ok()
+        .and_then(start_of_day)
+}
+
+fn priority_to_letter(priority: &str) -> Option<char> {
+    match priority {
+        "H" => Some('A'),
+        "M" => Some('B'),
+        "L" => Some('C'),
+        _ => None,
+    }
+}
+
+fn is_priority(word: &str) -> bool {
+    match word.strip_prefix('(').and_then(|word| word.strip_suffix(')')) {
+        Some(letter) => priority_from_letter(letter).is_some(),
+        None => false,
+    }
+}
+
+// Everything below (C) is low priority.
+fn priority_from_letter(letter: &str) -> Option<String> {
+    match letter {
+        "A" => Some("H".to_string()),
+        "B" => Some("M".to_string()),
+        _ if letter.len() == 1 && letter.chars().all(|char| char.is_ascii_uppercase()) => {
+            Some("L".to_string())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_todo_txt_round_trip() {
+    let todo_txt = "(A) 2026-09-20 Call Mom +Family @phone due:2026-10-01 rec:1w\n\
+        \n\
+        x 2026-10-01 2026-09-20 Water the plants @garden pri:B\n\
+        2026-09-21 Fix the fence +Home_Office +Garden state:blocked";
+    let records = from_todo_txt(todo_txt).expect("Failed to parse todo.txt");
+    assert_eq!(records.len(), 3);
+
+    let call = &records[0].task;
+    assert_eq!(call.title, "Call Mom rec:1w");
+    assert_eq!(call.priority.as_deref(), Some("H"));
+    assert_eq!(call.tags, vec!["phone"]);
+    assert_eq!(records[0].project.as_deref(), Some("Family"));
+    assert_eq!(call.due_utc().map(|due| due.with_timezone(&Local).day()), Some(1));
+
+    let water = &records[1].task;
+    assert_eq!(water.state.to_string(), "Finished");
+    assert_eq!(water.priority.as_deref(), Some("M"));
+    assert_eq!(water.ended_utc().map(|ended| ended.with_timezone(&Local).day()), Some(1));
+    assert_eq!(water.created_utc().map(|created| created.with_timezone(&Local).day()), Some(20));
+    let fence = &records[2];
+    assert_eq!(fence.task.state.to_string(), "Blocked");
+    assert_eq!(fence.task.title, "Fix the fence");
+    assert_eq!(fence.project.as_deref(), Some("Home Office"));
+    assert_eq!(fence.task.tags, vec!["Garden"]);
+
+    let exported = to_todo_txt(&records);
+    let lines: Vec<&str> = exported.lines().collect();
+    assert!(lines[0].starts_with("(A) 2026-09-20 Call Mom rec:1w +Family @phone due:2026-10-01 "));
+    assert!(lines[1].starts_with("x 2026-10-01 2026-09-20 Water the plants @garden pri:B uuid:"));
+
+    let reimported = from_todo_txt(&exported).expect("Failed to parse exported todo.txt");
+    for (reimported, record) in reimported.iter().zip(&records) {
+        assert_eq!(reimported.task.uuid, record.task.uuid);
+        assert_eq!(reimported.task.title, record.task.title);
+        assert_eq!(reimported.task.tags, record.task.tags);
+        assert_eq!(reimported.project, record.project);
+        assert_eq!(
+            reimported.task.state.to_string(),
+            record.task.state.to_string()
+        );
+    }
+}
@@ -0,0 +1,125 @@
+// workspace.rs
+
+use rusqlite::Connection;
+use std::fs;
+
+use crate::config::{overriding_layer, Config, CONFIG};
+use crate::database::{database_file, init_db};
+use crate::error::WorkspaceError;
+use crate::paths::data_dir;
+
+// Workspaces ______________________________________________________________________________________
+//
+// Every workspace is a task database of its own. The active one is picked in this order:
+//   pct --workspace <name>  >  workspace in the config layers (--config, $POCATO_WORKSPACE,
+//   .pocato.toml, config.toml ...)  >  "default"
+pub const DEFAULT_WORKSPACE: &str = "default";
+
+pub fn active_workspace(flag: Option<&str>) -> String {
+    match (flag, &CONFIG.workspace) {
+        (Some(workspace), _) => workspace.to_string(),
+        (None, Some(workspace)) => workspace.clone(),
+        (None, None) => DEFAULT_WORKSPACE.to_string(),
+    }
+}
+
+pub fn open_workspace(name: &str) -> Result<Connection, WorkspaceError> {
+    validate_name(name)?;
+    let file = database_file(name)?;
+    if name != DEFAULT_WORKSPACE && !file.is_file() {
+        return Err(WorkspaceError::NotFound(format!(
+            "There is no workspace '{}', create it with: pct workspace create {}",
+            name, name
+        )));
+    }
+    Ok(init_db(&file)?)
+}
+
+// Public workspace commands _______________________________________________________________________
+pub fn list_workspaces() -> Result<String, WorkspaceError> {
+    let active = active_workspace(None);
+    let lines: Vec<String> = workspace_names()?
+        .into_iter()
+        .map(|name| match name == active {
+            true => format!("* \x1b[1;34m{}\x1b[0m", name),
+            false => format!("  {}", name),
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+pub fn create_workspace(name: &str) -> Result<String, WorkspaceError> {
+    validate_name(name)?;
+    if workspace_names()?.iter().any(|workspace| workspace == name) {
+        return Err(WorkspaceError::AlreadyExists(format!(
+            "The workspace '{}' already exists",
+            name
+        )));
+    }
+    init_db(&database_file(name)?)?;
+    Ok(format!(
+        "Created workspace \x1b[1;34m{}\x1b[0m\nSwitch to it with: pct workspace switch {}",
+        name, name
+    ))
+}
+
+// Store the workspace in config.toml, so it's used from now on.
+pub fn switch_workspace(name: &str) -> Result<String, WorkspaceError> {
+    validate_name(name)?;
+    if !workspace_names()?.iter().any(|workspace| workspace == name) {
+        return Err(WorkspaceError::NotFound(format!(
+            "There is no workspace '{}', create it with: pct workspace create {}",
+            name, name
+        )));
+    }
+    match name {
+        DEFAULT_WORKSPACE => Config::set_value("workspace", None)?,
+        _ => Config::set_value("workspace", Some(name.into()))?,
+    }
+    let mut message = format!("Switched to workspace \x1b[1;34m{}\x1b[0m", name);
+    // $POCATO_WORKSPACE, a .pocato.toml or --config set the workspace after config.toml:
+    if let Some(origin) = overriding_layer("workspace") {
+        message.push_str(&format!(
+            "\n\x1b[33m{} sets the workspace and still takes precedence.\x1b[0m",
+            origin
+        ));
+    }
+    Ok(message)
+}
+
+// Helper functions --------------------------------------------------------------------------------
+pub fn workspace_names() -> Result<Vec<String>, WorkspaceError> {
+    let mut names = vec![DEFAULT_WORKSPACE.to_string()];
+    let dir = data_dir()?.join("workspaces");
+    if dir.is_dir() {
+        let mut others: Vec<String> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "db"))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+            .collect();
+        others.sort();
+        names.extend(others);
+    }
+    Ok(names)
+}
+
+// Names become file names, so only letters, digits, '-' and '_' are allowed.
+fn validate_name(name: &str) -> Result<(), WorkspaceError> {
+    let valid_characters = name
+        .chars()
+        .all(|char| char.is_alphanumeric() || char == '-' || char == '_');
+    match (name.is_empty(), valid_characters, name) {
+        (true, _, _) => Err(WorkspaceError::InvalidName(
+            "Please enter a workspace name".to_string(),
+        )),
+        (false, false, _) => Err(WorkspaceError::InvalidName(format!(
+            "'{}' may only contain letters, digits, '-' and '_'",
+            name
+        ))),
+        // Backups are named after the database file, "tasks" belongs to the default workspace:
+        (false, true, "tasks") => Err(WorkspaceError::InvalidName(
+            "'tasks' is reserved, please choose another name".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,346 @@
+// test.rs
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// Run the pct binary against a throwaway config & data directory __________________________________
+fn pct(home: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pct"))
+        .args(args)
+        .env("POCATO_DIR", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("HOME", home)
+        .output()
+        .expect("Failed to run pct")
+}
+
+fn temp_home(name: &str) -> PathBuf {
+    let home = std::env::temp_dir().join(format!("pocato-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(home.join("data")).expect("Failed to create test directory");
+    home
+}
+
+#[test]
+fn test_add_and_show() {
+    let home = temp_home("add-and-show");
+
+    // Checking the config doesn't create one:
+    let output = pct(&home, &["config", "validate"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("the defaults are used"));
+    assert!(!home.join("config/config.toml").exists());
+
+    let output = pct(&home, &["add", "Water", "the", "plants"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Water the plants") && stdout.contains("(#1)"));
+
+    let output = pct(&home, &["show"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Water the plants"));
+    assert!(home.join("config/config.toml").is_file());
+    assert!(home.join("data/pocato/tasks.db").is_file());
+}
+
+#[test]
+fn test_backup_and_restore() {
+    let home = temp_home("backup-and-restore");
+    let backup = home.join("backup.db");
+
+    pct(&home, &["add", "Water", "the", "plants"]);
+    let output = pct(&home, &["backup", backup.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert!(backup.is_file());
+
+    pct(&home, &["add", "Paint", "the", "fence"]);
+    let output = pct(&home, &["restore", backup.to_str().unwrap()]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Restored tasks"));
+
+    let stdout = String::from_utf8_lossy(&pct(&home, &["show"]).stdout).to_string();
+    assert!(stdout.contains("Water the plants") && !stdout.contains("Paint the fence"));
+    // The tasks from before the restore are kept in a backup as well:
+    let backups = std::fs::read_dir(home.join("data/pocato/backups")).unwrap();
+    assert_eq!(backups.count(), 1);
+
+    let output = pct(&home, &["restore", home.join("config/config.toml").to_str().unwrap()]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid Backup"));
+
+    // The daily backup is made before the first change, not when <TAB> asks for completions:
+    let daily = home.join("data/pocato/backups/daily");
+    pct(&home, &["--config", "backup.keep_daily=1", "__complete", "fin"]);
+    pct(&home, &["--config", "backup.keep_daily=1", "show"]);
+    assert!(!daily.exists());
+    pct(&home, &["--config", "backup.keep_daily=1", "add", "Mow", "the", "lawn"]);
+    assert_eq!(std::fs::read_dir(&daily).unwrap().count(), 1);
+}
+
+#[test]
+fn test_workspaces() {
+    let home = temp_home("workspaces");
+
+    pct(&home, &["add", "Water", "the", "plants"]);
+    let output = pct(&home, &["--workspace", "work", "show"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("There is no workspace 'work'"));
+
+    assert!(pct(&home, &["workspace", "create", "work"]).status.success());
+    pct(&home, &["--workspace", "work", "add", "Write", "the", "report"]);
+    let stdout = String::from_utf8_lossy(&pct(&home, &["show"]).stdout).to_string();
+    assert!(stdout.contains("Water the plants") && !stdout.contains("Write the report"));
+
+    pct(&home, &["workspace", "switch", "work"]);
+    let config = std::fs::read_to_string(home.join("config/config.toml")).unwrap();
+    assert!(config.contains("workspace = \"work\""));
+    let stdout = String::from_utf8_lossy(&pct(&home, &["show"]).stdout).to_string();
+    assert!(stdout.contains("Write the report") && !stdout.contains("Water the plants"));
+
+    let stdout = String::from_utf8_lossy(&pct(&home, &["workspace", "list"]).stdout).to_string();
+    assert!(stdout.contains("  default") && stdout.contains("* "));
+}
+
+#[test]
+fn test_data_dir() {
+    let home = temp_home("data-dir");
+    let run = |envs: &[(&str, PathBuf)]| {
+        Command::new(env!("CARGO_BIN_EXE_pct"))
+            .args(["add", "Water", "the", "plants"])
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("POCATO_DATA_DIR")
+            .env("HOME", &home)
+            .env("POCATO_DIR", home.join("config"))
+            .envs(envs.iter().cloned())
+            .output()
+            .expect("Failed to run pct")
+    };
+
+    // Without any data dir variable the database moves in with the config:
+    assert!(run(&[]).status.success());
+    assert!(home.join("config/data/tasks.db").is_file());
+
+    assert!(run(&[("POCATO_DATA_DIR", home.join("elsewhere"))]).status.success());
+    assert!(home.join("elsewhere/tasks.db").is_file());
+
+    // A data dir that can't be created is an error message, not a panic:
+    std::fs::write(home.join("file"), "").unwrap();
+    let output = run(&[("POCATO_DATA_DIR", home.join("file/data"))]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Can't access") && !stderr.contains("panicked"));
+}
+
+#[test]
+fn test_legacy_data_dir() {
+    let home = temp_home("legacy-data-dir");
+    let run = |args: &[&str], data_home: Option<PathBuf>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_pct"));
+        command
+            .args(args)
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("POCATO_DATA_DIR")
+            .env("HOME", &home)
+            .env("POCATO_DIR", home.join("config"));
+        if let Some(data_home) = data_home {
+            command.env("XDG_DATA_HOME", data_home);
+        }
+        command.output().expect("Failed to run pct")
+    };
+
+    // Tasks from before $POCATO_DIR/data existed stay where they are:
+    let legacy_dir = home.join(".local/share");
+    run(&["add", "Water", "the", "plants"], Some(legacy_dir.clone()));
+    assert!(legacy_dir.join("pocato/tasks.db").is_file());
+    let output = run(&["show"], None);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Water the plants"));
+    assert!(!home.join("config/data/tasks.db").exists());
+}
+
+#[test]
+fn test_color() {
+    let home = temp_home("color");
+    pct(&home, &["add", "Water", "the", "plants"]);
+
+    // Output to a pipe has no colors, unless they are asked for:
+    let stdout = String::from_utf8_lossy(&pct(&home, &["show"]).stdout).to_string();
+    assert!(stdout.contains("│ Task ") && !stdout.contains('\x1b'));
+    let output = pct(&home, &["--color", "always", "start", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\x1b[1;34mWater the plants\x1b[0m"));
+    let output = pct(&home, &["show", "5"]);
+    assert!(!String::from_utf8_lossy(&output.stderr).contains('\x1b'));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pct"))
+        .args(["--color", "auto", "show"])
+        .env("POCATO_DIR", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("CLICOLOR_FORCE", "1")
+        .env_remove("NO_COLOR")
+        .output()
+        .expect("Failed to run pct");
+    assert!(String::from_utf8_lossy(&output.stdout).contains('\x1b'));
+}
+
+#[test]
+fn test_config_layers() {
+    let home = temp_home("config-layers");
+    let repo = home.join("repo");
+    std::fs::create_dir_all(repo.join("src")).unwrap();
+    std::fs::write(repo.join(".pocato.toml"), "default_project = \"Website\"\n").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_pct"))
+            .args(args)
+            .current_dir(repo.join("src"))
+            .env("POCATO_DIR", home.join("config"))
+            .env("XDG_DATA_HOME", home.join("data"))
+            .env("HOME", &home)
+            .env("POCATO_AUTO_ARCHIVE", "14d")
+            .output()
+            .expect("Failed to run pct")
+    };
+
+    // The .pocato.toml of the repository is found from a subfolder:
+    let output = run(&["add", "Fix", "the", "footer"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Added new task to Website:"));
+
+    let output = run(&["--config", "theme=solarized", "config", "list", "--show-origin"]);
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let pocato_toml = repo.join(".pocato.toml").display().to_string();
+    assert!(stdout.lines().any(|line| line.starts_with(&pocato_toml)
+        && line.ends_with("default_project = \"Website\"")));
+    assert!(stdout.contains("$POCATO_AUTO_ARCHIVE") && stdout.contains("auto_archive = \"14d\""));
+    assert!(stdout.lines().any(|line| line.starts_with("--config")
+        && line.ends_with("theme = \"solarized\"")));
+
+    let output = run(&["--config", "nerd_font=maybe", "config", "get", "theme"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--config nerd_font=maybe"));
+
+    // A workspace pinned by the repository wins over `pct workspace switch`:
+    std::fs::write(repo.join(".pocato.toml"), "workspace = \"default\"\n").unwrap();
+    run(&["workspace", "create", "work"]);
+    let stdout = String::from_utf8_lossy(&run(&["workspace", "switch", "work"]).stdout).to_string();
+    assert!(stdout.contains(&format!("{} sets the workspace", pocato_toml)));
+
+    // A repository can't send the tasks or their database elsewhere:
+    for setting in ["caldav.url = \"https://evil.example/\"", "database_path = \"/tmp\""] {
+        std::fs::write(repo.join(".pocato.toml"), setting).unwrap();
+        let output = run(&["config", "validate"]);
+        assert!(!output.status.success());
+        let key = setting.split(['.', ' ']).next().unwrap();
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains(&format!("'{}' can't be set for a project", key)));
+    }
+}
+
+#[test]
+fn test_completions() {
+    let home = temp_home("completions");
+
+    pct(&home, &["add", "Water", "the", "plants", "+garden"]);
+    pct(&home, &["workspace", "create", "work"]);
+    pct(&home, &["--workspace", "work", "add", "Write", "the", "report"]);
+    let stdout = String::from_utf8_lossy(&pct(&home, &["__complete", "finish", ""]).stdout)
+        .to_string();
+    assert_eq!(stdout.trim_end(), "1\tWater the plants");
+    let output = pct(&home, &["__complete", "--workspace", "work", "finish", ""]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "1\tWrite the report");
+    let output = pct(&home, &["__complete", "+g"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "+garden\ttag, 1 task");
+
+    for shell in ["bash", "zsh", "fish"] {
+        let output = pct(&home, &["completions", shell]);
+        assert!(String::from_utf8_lossy(&output.stdout).contains("pct __complete"));
+    }
+    let output = pct(&home, &["completions", "tcsh"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown shell 'tcsh'"));
+}
+
+#[test]
+fn test_shell() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let home = temp_home("shell");
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_pct"))
+        .arg("shell")
+        .env("POCATO_DIR", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run pct shell");
+    let lines = "add Water the plants\nworkspace create work\nuse work\nadd Write the report\n\
+        show\nuse nope\nexit\n";
+    shell.stdin.take().unwrap().write_all(lines.as_bytes()).unwrap();
+    let output = shell.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    assert!(stdout.contains("Using workspace work"));
+    let shown = stdout.rsplit("Task").next().unwrap();
+    assert!(shown.contains("Write the report") && !shown.contains("Water the plants"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("There is no workspace 'nope'"));
+
+    // Each line ran once in the workspace that was open at the time:
+    let stdout = String::from_utf8_lossy(&pct(&home, &["show"]).stdout).to_string();
+    assert!(stdout.contains("Water the plants") && !stdout.contains("Write the report"));
+}
+
+#[test]
+fn test_pick_without_terminal() {
+    let home = temp_home("pick");
+
+    pct(&home, &["add", "Water", "the", "plants"]);
+    // Without an id or filter the task would be picked from a list, which needs a terminal:
+    let output = pct(&home, &["finish"]);
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    assert!(stderr.contains("Which task? Enter its id, e.g. pct finish 3 or pct 3 finish"));
+    let output = pct(&home, &["modify", "priority:H"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("pct modify 3"));
+    assert!(pct(&home, &["finish", "1"]).status.success());
+}
+
+#[test]
+fn test_confirmations_and_exit_codes() {
+    let home = temp_home("confirm");
+
+    pct(&home, &["add", "Old", "task"]);
+    pct(&home, &["add", "Another", "old", "task"]);
+    pct(&home, &["add", "Keep", "me"]);
+    // Without a terminal the deletion can't be confirmed, nothing is deleted:
+    let output = pct(&home, &["delete", "1"]);
+    assert_eq!(output.status.code(), Some(4));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Add --yes to confirm"));
+    assert!(pct(&home, &["info", "1"]).status.success());
+    // A filter asks once for all of its tasks:
+    let output = pct(&home, &["1-3", "delete"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Delete 3 tasks?"));
+    // When one of them fails, the ones already changed are still reported:
+    let output = pct(&home, &["1-2", "modify", "Shared", "title"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Shared title (#2)"));
+
+    let output = pct(&home, &["--yes", "delete", "1"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Deleted:"));
+    // The ids move up, "Keep me" is #2 now:
+    let output = pct(&home, &["--config", "confirm_destructive=false", "delete", "1"]);
+    assert!(output.status.success());
+
+    // --no-input fails where the task picker would be shown:
+    assert_eq!(pct(&home, &["--no-input", "finish"]).status.code(), Some(4));
+    assert_eq!(pct(&home, &["start", "not_a_number"]).status.code(), Some(2));
+    let output = pct(&home, &["--no-input", "finish", "1"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Keep me"));
+}
+
+#[test]
+fn test_complete_keeps_tasks() {
+    let home = temp_home("complete-keeps-tasks");
+
+    let done = home.join("done.txt");
+    std::fs::write(&done, "x 2020-01-01 Water the plants").expect("Failed to write todo.txt");
+    pct(&home, &["import", done.to_str().unwrap(), "--from", "todo-txt"]);
+    // Pressing <TAB> neither archives the finished task nor drops its id:
+    let output = pct(&home, &["--config", "auto_archive=1d", "__complete", "info", ""]);
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("1\t"));
+    assert!(pct(&home, &["info", "1"]).status.success());
+
+    pct(&home, &["--config", "auto_archive=1d", "show"]);
+    assert!(!pct(&home, &["info", "1"]).status.success());
+}